@@ -0,0 +1,451 @@
+//! A small fake of the AWS endpoints assumer talks to: the SSO portal API
+//! (`ListAccounts`, `ListAccountRoles`, `GetRoleCredentials`), SSO OIDC
+//! (`RegisterClient`, `StartDeviceAuthorization`, `CreateToken`), STS
+//! `AssumeRole` and the federation sign-in `getSigninToken` action. Every
+//! service is served from the same address, so all of assumer's
+//! `*_endpoint_url` options can point at [FakeAws::url].
+//!
+//! Device authorizations are approved immediately, any bearer token is
+//! accepted and `AssumeRole` succeeds for any role ARN as long as the
+//! caller signs with credentials handed out by `GetRoleCredentials` and
+//! sends a token code along with an MFA serial number. It speaks just
+//! enough HTTP/1.1 for the AWS SDK and reqwest.
+
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde_json::{json, Value};
+
+pub const ACCESS_TOKEN: &str = "fake-access-token";
+pub const REFRESH_TOKEN: &str = "fake-refresh-token";
+pub const SIGNIN_TOKEN: &str = "fake-signin-token";
+
+#[derive(Clone, Debug)]
+pub struct Account {
+    pub id: String,
+    pub name: String,
+    pub email: String,
+    pub roles: Vec<String>,
+}
+
+#[derive(Default)]
+struct State {
+    accounts: Vec<Account>,
+    page_size: usize,
+    throttle: usize,
+    forbidden_role_lists: Vec<String>,
+    requests: Vec<String>,
+    assume_role_requests: Vec<HashMap<String, String>>,
+}
+
+#[derive(Default)]
+pub struct Builder {
+    accounts: Vec<Account>,
+    page_size: Option<usize>,
+}
+
+impl Builder {
+    pub fn account(mut self, id: &str, name: &str, email: &str, roles: &[&str]) -> Self {
+        self.accounts.push(Account {
+            id: id.to_string(),
+            name: name.to_string(),
+            email: email.to_string(),
+            roles: roles.iter().map(|role| role.to_string()).collect(),
+        });
+        self
+    }
+
+    /// Caps how many items a list call returns at once, to exercise
+    /// pagination. Defaults to whatever the client asks for.
+    pub fn page_size(mut self, page_size: usize) -> Self {
+        self.page_size = Some(page_size);
+        self
+    }
+
+    /// Starts the fake on a random local port.
+    pub fn start(self) -> FakeAws {
+        self.start_on("127.0.0.1:0")
+    }
+
+    pub fn start_on(self, addr: &str) -> FakeAws {
+        let listener = TcpListener::bind(addr).expect("could not bind fake-aws listener");
+        let addr = listener.local_addr().unwrap();
+        let state = Arc::new(Mutex::new(State {
+            accounts: self.accounts,
+            page_size: self.page_size.unwrap_or(usize::MAX),
+            ..State::default()
+        }));
+
+        let server_state = state.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let state = server_state.clone();
+                std::thread::spawn(move || {
+                    let url = format!("http://{}", addr);
+                    let _ = handle_connection(stream, &state, &url);
+                });
+            }
+        });
+
+        FakeAws { addr, state }
+    }
+}
+
+pub struct FakeAws {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+}
+
+impl FakeAws {
+    pub fn builder() -> Builder {
+        Builder::default()
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Rejects the next `count` SSO portal calls with
+    /// `TooManyRequestsException`.
+    pub fn throttle_next(&self, count: usize) {
+        self.state.lock().unwrap().throttle = count;
+    }
+
+    /// Rejects every `ListAccountRoles` call for `account_id` with
+    /// `ForbiddenException`.
+    pub fn forbid_role_list(&self, account_id: &str) {
+        self.state.lock().unwrap().forbidden_role_lists.push(account_id.to_string());
+    }
+
+    /// The operations served so far, e.g. `ListAccountRoles`, in order.
+    pub fn requests(&self) -> Vec<String> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    /// The form parameters of every `AssumeRole` call so far, e.g.
+    /// `SerialNumber` or `Tags.member.1.Key`.
+    pub fn assume_role_requests(&self) -> Vec<HashMap<String, String>> {
+        self.state.lock().unwrap().assume_role_requests.clone()
+    }
+}
+
+struct Request {
+    method: String,
+    path: String,
+    query: HashMap<String, String>,
+    headers: HashMap<String, String>,
+    body: Value,
+    form: HashMap<String, String>,
+}
+
+struct Response {
+    status: u16,
+    error_type: Option<&'static str>,
+    content_type: &'static str,
+    body: String,
+}
+
+impl Response {
+    fn ok(body: Value) -> Self {
+        Response { status: 200, error_type: None, content_type: "application/json", body: body.to_string() }
+    }
+
+    fn error(status: u16, error_type: &'static str, message: &str) -> Self {
+        Response {
+            status,
+            error_type: Some(error_type),
+            content_type: "application/json",
+            body: json!({ "message": message }).to_string(),
+        }
+    }
+
+    /// STS speaks the AWS query protocol, which answers in XML.
+    fn xml(status: u16, body: String) -> Self {
+        Response { status, error_type: None, content_type: "text/xml", body }
+    }
+}
+
+fn handle_connection(stream: TcpStream, state: &Mutex<State>, url: &str) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut stream = stream;
+
+    while let Some(request) = read_request(&mut reader)? {
+        let response = route(&request, state, url);
+        let body = response.body;
+        let mut head = format!(
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n",
+            response.status,
+            if response.status == 200 { "OK" } else { "Error" },
+            response.content_type,
+            body.len(),
+        );
+        if let Some(error_type) = response.error_type {
+            head.push_str(&format!("x-amzn-errortype: {}\r\n", error_type));
+        }
+        head.push_str("\r\n");
+        stream.write_all(head.as_bytes())?;
+        stream.write_all(body.as_bytes())?;
+        stream.flush()?;
+    }
+
+    Ok(())
+}
+
+fn read_request(reader: &mut BufReader<TcpStream>) -> std::io::Result<Option<Request>> {
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line)? == 0 {
+        return Ok(None);
+    }
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let target = parts.next().unwrap_or_default().to_string();
+
+    let mut headers = HashMap::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+        }
+    }
+
+    let length = headers.get("content-length").and_then(|l| l.parse().ok()).unwrap_or(0);
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+
+    let (path, query) = target.split_once('?').unwrap_or((&target, ""));
+    let is_form = headers.get("content-type").is_some_and(|t| t.starts_with("application/x-www-form-urlencoded"));
+    Ok(Some(Request {
+        method,
+        path: path.to_string(),
+        query: parse_query(query),
+        headers,
+        form: if is_form { parse_query(&String::from_utf8_lossy(&body)) } else { HashMap::new() },
+        body: serde_json::from_slice(&body).unwrap_or(Value::Null),
+    }))
+}
+
+fn route(request: &Request, state: &Mutex<State>, url: &str) -> Response {
+    let mut state = state.lock().unwrap();
+    let operation = match (request.method.as_str(), request.path.as_str()) {
+        ("POST", "/client/register") => "RegisterClient",
+        ("POST", "/device_authorization") => "StartDeviceAuthorization",
+        ("POST", "/token") => "CreateToken",
+        ("GET", "/assignment/accounts") => "ListAccounts",
+        ("GET", "/assignment/roles") => "ListAccountRoles",
+        ("GET", "/federation/credentials") => "GetRoleCredentials",
+        ("GET", "/federation") => "GetSigninToken",
+        ("POST", "/") if request.form.get("Action").map(String::as_str) == Some("AssumeRole") => "AssumeRole",
+        _ => return Response::error(404, "ResourceNotFoundException", "Unknown operation"),
+    };
+    state.requests.push(operation.to_string());
+
+    let is_portal_call = matches!(operation, "ListAccounts" | "ListAccountRoles" | "GetRoleCredentials");
+    if is_portal_call {
+        if state.throttle > 0 {
+            state.throttle -= 1;
+            return Response::error(429, "TooManyRequestsException", "Rate exceeded");
+        }
+        if request.headers.get("x-amz-sso_bearer_token").is_none_or(|token| token.is_empty()) {
+            return Response::error(401, "UnauthorizedException", "Session token not found or invalid");
+        }
+    }
+
+    let now = now_seconds();
+    match operation {
+        "RegisterClient" => Response::ok(json!({
+            "clientId": "fake-client-id",
+            "clientSecret": "fake-client-secret",
+            "clientIdIssuedAt": now,
+            "clientSecretExpiresAt": now + 90 * 24 * 3600,
+        })),
+        "StartDeviceAuthorization" => Response::ok(json!({
+            "deviceCode": "fake-device-code",
+            "userCode": "FAKE-CODE",
+            "verificationUri": format!("{}/device", url),
+            "verificationUriComplete": format!("{}/device?user_code=FAKE-CODE", url),
+            "expiresIn": 600,
+            "interval": 1,
+        })),
+        "CreateToken" => {
+            let grant_type = request.body.get("grantType").and_then(Value::as_str).unwrap_or_default();
+            if grant_type == "refresh_token" && request.body.get("refreshToken").and_then(Value::as_str) != Some(REFRESH_TOKEN) {
+                return Response::error(400, "InvalidGrantException", "Invalid refresh token");
+            }
+            Response::ok(json!({
+                "accessToken": ACCESS_TOKEN,
+                "tokenType": "Bearer",
+                "expiresIn": 8 * 3600,
+                "refreshToken": REFRESH_TOKEN,
+            }))
+        }
+        "ListAccounts" => {
+            let accounts = state.accounts.iter()
+                .map(|account| json!({
+                    "accountId": account.id,
+                    "accountName": account.name,
+                    "emailAddress": account.email,
+                }))
+                .collect::<Vec<_>>();
+            let (page, next_token) = paginate(&accounts, request, state.page_size);
+            Response::ok(json!({ "accountList": page, "nextToken": next_token }))
+        }
+        "ListAccountRoles" => {
+            let account_id = request.query.get("account_id").cloned().unwrap_or_default();
+            let Some(account) = state.accounts.iter().find(|account| account.id == account_id) else {
+                return Response::error(404, "ResourceNotFoundException", "Account not found");
+            };
+            if state.forbidden_role_lists.contains(&account_id) {
+                return Response::error(403, "ForbiddenException", "No access");
+            }
+            let roles = account.roles.iter()
+                .map(|role| json!({ "roleName": role, "accountId": account.id }))
+                .collect::<Vec<_>>();
+            let (page, next_token) = paginate(&roles, request, state.page_size);
+            Response::ok(json!({ "roleList": page, "nextToken": next_token }))
+        }
+        "GetRoleCredentials" => {
+            let account_id = request.query.get("account_id").cloned().unwrap_or_default();
+            let role_name = request.query.get("role_name").cloned().unwrap_or_default();
+            let assigned = state.accounts.iter()
+                .any(|account| account.id == account_id && account.roles.contains(&role_name));
+            if !assigned {
+                return Response::error(403, "ForbiddenException", "No access");
+            }
+            Response::ok(json!({
+                "roleCredentials": {
+                    "accessKeyId": format!("ASIAFAKE{}", account_id),
+                    "secretAccessKey": format!("fake-secret-{}-{}", account_id, role_name),
+                    "sessionToken": format!("fake-session-{}-{}", account_id, role_name),
+                    "expiration": (now + 3600) * 1000,
+                }
+            }))
+        }
+        "GetSigninToken" => Response::ok(json!({ "SigninToken": SIGNIN_TOKEN })),
+        "AssumeRole" => {
+            state.assume_role_requests.push(request.form.clone());
+            assume_role(request, now)
+        }
+        _ => unreachable!(),
+    }
+}
+
+/// Hands out credentials derived from the request: the access key carries
+/// the target account, the session token the session name and external ID.
+fn assume_role(request: &Request, now: u64) -> Response {
+    let signed_with_sso_credentials = request.headers.get("authorization")
+        .is_some_and(|auth| auth.contains("Credential=ASIAFAKE"));
+    if !signed_with_sso_credentials {
+        return sts_error(403, "AccessDenied", "Not signed with role credentials");
+    }
+    let field = |name: &str| request.form.get(name).cloned().unwrap_or_default();
+    if !field("SerialNumber").is_empty() && field("TokenCode").is_empty() {
+        return sts_error(403, "AccessDenied", "MultiFactorAuthentication failed, no token code");
+    }
+    let role_arn = field("RoleArn");
+    let Some(account_id) = role_arn.strip_prefix("arn:aws:iam::").and_then(|rest| rest.split(':').next()) else {
+        return sts_error(400, "ValidationError", "Invalid RoleArn");
+    };
+    let role_name = role_arn.rsplit('/').next().unwrap_or_default();
+    let session_name = field("RoleSessionName");
+    let duration: u64 = field("DurationSeconds").parse().unwrap_or(3600);
+
+    Response::xml(200, format!(
+        "<AssumeRoleResponse xmlns=\"https://sts.amazonaws.com/doc/2011-06-15/\">\
+           <AssumeRoleResult>\
+             <Credentials>\
+               <AccessKeyId>ASIACHAIN{account_id}</AccessKeyId>\
+               <SecretAccessKey>fake-chained-secret-{role_name}</SecretAccessKey>\
+               <SessionToken>fake-chained-session-{session_name}-{external_id}</SessionToken>\
+               <Expiration>{expiration}</Expiration>\
+             </Credentials>\
+             <AssumedRoleUser>\
+               <AssumedRoleId>AROAFAKE:{session_name}</AssumedRoleId>\
+               <Arn>arn:aws:sts::{account_id}:assumed-role/{role_name}/{session_name}</Arn>\
+             </AssumedRoleUser>\
+           </AssumeRoleResult>\
+           <ResponseMetadata><RequestId>fake-request-id</RequestId></ResponseMetadata>\
+         </AssumeRoleResponse>",
+        external_id = field("ExternalId"),
+        expiration = iso8601(now + duration),
+    ))
+}
+
+fn sts_error(status: u16, code: &str, message: &str) -> Response {
+    Response::xml(status, format!(
+        "<ErrorResponse><Error><Type>Sender</Type><Code>{}</Code><Message>{}</Message></Error>\
+         <RequestId>fake-request-id</RequestId></ErrorResponse>",
+        code, message,
+    ))
+}
+
+/// Formats epoch seconds as `YYYY-MM-DDTHH:MM:SSZ`.
+fn iso8601(seconds: u64) -> String {
+    let days = (seconds / 86400) as i64;
+    let time = seconds % 86400;
+    // Civil-from-days, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year, month, day, time / 3600, time % 3600 / 60, time % 60,
+    )
+}
+
+fn paginate(items: &[Value], request: &Request, page_size: usize) -> (Vec<Value>, Option<String>) {
+    let start: usize = request.query.get("next_token").and_then(|t| t.parse().ok()).unwrap_or(0);
+    let max_results: usize = request.query.get("max_result").and_then(|m| m.parse().ok()).unwrap_or(usize::MAX);
+    let end = items.len().min(start.saturating_add(max_results.min(page_size)));
+    let next_token = (end < items.len()).then(|| end.to_string());
+    (items[start.min(end)..end].to_vec(), next_token)
+}
+
+fn parse_query(query: &str) -> HashMap<String, String> {
+    query.split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(key, value)| (decode(key), decode(value)))
+        .collect()
+}
+
+fn decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' if i + 3 <= bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or("");
+                match u8::from_str_radix(hex, 16) {
+                    Ok(byte) => {
+                        decoded.push(byte);
+                        i += 3;
+                        continue;
+                    }
+                    Err(_) => decoded.push(b'%'),
+                }
+            }
+            b'+' => decoded.push(b' '),
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+fn now_seconds() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+}
@@ -0,0 +1,16 @@
+use fake_aws::FakeAws;
+
+/// Runs the fake on a fixed port with a couple of demo accounts, so assumer
+/// can be pointed at it by hand through the `*_endpoint_url` config options.
+fn main() {
+    let port = std::env::args().nth(1).unwrap_or_else(|| "4566".to_string());
+    let server = FakeAws::builder()
+        .account("111111111111", "sandbox", "sandbox@example.com", &["AdministratorAccess", "ReadOnlyAccess"])
+        .account("222222222222", "production", "production@example.com", &["ReadOnlyAccess"])
+        .start_on(&format!("127.0.0.1:{}", port));
+
+    println!("fake-aws listening on {}", server.url());
+    loop {
+        std::thread::park();
+    }
+}
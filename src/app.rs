@@ -1,7 +1,7 @@
-use std::{collections::HashMap, rc::Rc};
-use crate::{aws::AccountInfo, sso, tui, widgets::{self}};
+use std::{collections::{BTreeMap, HashMap}, path::PathBuf, rc::Rc, sync::mpsc::Receiver, time::{Duration, Instant}};
+use crate::{browser, console, profiles, utils::{ini_file::IniFile, mask}, clipboard, exporters::{self, ExportContext, Exporter}, aws::{self, AccountInfo, CredentialsFile, InventoryCache, RoleCredentialsCache}, jobs::{self, JobEvent}, metadata::{AccountMetadata, MetadataStore}, sso, tui, widgets::{self}};
+use chrono::{DateTime, Utc};
 use directories::UserDirs;
-use ini::Ini;
 use ratatui::{
     layout::Rect, widgets::{
         ScrollbarState, TableState,
@@ -10,18 +10,22 @@ use ratatui::{
     Frame
 };
 use color_eyre::{
-    eyre::{Error, WrapErr},
+    eyre::{eyre, Error, WrapErr},
     Result,
 };
 use crate::sso::{ConfigProvider, RoleCredentials};
 
 const ITEM_HEIGHT: usize = 4;
+const TICK_RATE: Duration = Duration::from_millis(100);
 
 #[derive(Clone, Eq, PartialEq, Hash)]
 pub enum CurrentPage{
     AccountList,
     Config,
     Credentials,
+    Metadata,
+    Prune,
+    Review,
     Roles
 }
 
@@ -31,11 +35,56 @@ pub struct RouteConfig {
     pub render: fn(&mut Frame, &mut App, Rect),
 }
 
+/// File changes shown as a diff, waiting for the user to confirm them.
+pub struct Review {
+    pub title: String,
+    pub warnings: Vec<String>,
+    pub changes: Vec<(PathBuf, String, String)>,
+    /// Shown once the changes have been written.
+    pub done_message: String,
+    pub scroll: u16,
+}
+
 #[derive(Default, Clone)]
 pub struct AccountRow {
     pub account_name: String,
     pub account_id: String,
+    pub email: String,
     pub roles: Vec<String>,
+    pub throttled: bool,
+}
+
+impl AccountRow {
+    /// Case-insensitive match of `query` against the account name, ID, email
+    /// and any of its known roles.
+    pub fn matches(&self, query: &str) -> bool {
+        let query = query.to_lowercase();
+        self.account_name.to_lowercase().contains(&query)
+            || self.account_id.contains(&query)
+            || self.email.to_lowercase().contains(&query)
+            || self.roles.iter().any(|role| role.to_lowercase().contains(&query))
+    }
+
+    pub fn account_info(&self) -> AccountInfo {
+        AccountInfo {
+            account_name: self.account_name.clone(),
+            account_id: self.account_id.clone(),
+            roles: self.roles.clone(),
+            email: self.email.clone(),
+        }
+    }
+}
+
+impl From<AccountInfo> for AccountRow {
+    fn from(account: AccountInfo) -> Self {
+        AccountRow {
+            account_name: account.account_name,
+            account_id: account.account_id,
+            email: account.email,
+            roles: account.roles,
+            throttled: false,
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -49,10 +98,27 @@ pub struct ConfigOptions {
     pub options: Vec<ConfigOption>,
 }
 
-#[derive(Clone)]
+impl ConfigOptions {
+    pub fn value(&self, name: &str) -> String {
+        self.options.iter()
+            .find(|option| option.name == name)
+            .map(|option| option.value.clone())
+            .unwrap_or_default()
+    }
+}
+
 pub struct App {
     pub table_state: TableState,
     pub rows: Vec<AccountRow>,
+    pub visible_rows: Vec<usize>,
+    pub search_query: String,
+    pub searching: bool,
+    pub arn_input: String,
+    pub entering_arn: bool,
+    /// Token code typed for a chained role that needs MFA.
+    pub mfa_code_input: String,
+    pub entering_mfa_code: bool,
+    pub show_email: bool,
     pub exit: bool,    
     pub scroll_state: ScrollbarState,
     pub selected_account: AccountRow,
@@ -61,15 +127,46 @@ pub struct App {
     pub role_is_selected: bool,
     pub selected_role: String,
     pub role_credentials: RoleCredentials,
+    pub credentials_error: Option<String>,
+    /// Why the selected account's roles couldn't be listed.
+    pub roles_error: Option<String>,
+    /// The key to press again to go ahead with an action that was warned
+    /// about.
+    pub pending_confirmation: Option<char>,
+    pub export_format: String,
+    pub format_picker_open: bool,
+    pub format_table_state: TableState,
+    pub console_picker_open: bool,
+    pub console_table_state: TableState,
+    /// When to clear the clipboard, and the digest of what we put there.
+    pub clipboard_clear: Option<(Instant, String, clipboard::Backend)>,
+    pub secrets_revealed_until: Option<Instant>,
+    pub presentation_mode: bool,
+    pub credential_cache: RoleCredentialsCache,
     pub credential_message: String,
     pub aws_config_provider: ConfigProvider,
     pub config_table_state: TableState,
     pub value_input: String,
     pub currently_editing: bool,
+    pub metadata: MetadataStore,
+    pub editing_metadata: bool,
+    pub metadata_form: Vec<ConfigOption>,
+    pub metadata_table_state: TableState,
+    pub pruning: bool,
+    pub prune_profiles: Vec<(profiles::ManagedProfile, bool)>,
+    pub prune_table_state: TableState,
+    pub review: Option<Review>,
     pub token_prompt: String,
     pub current_page: CurrentPage,
     pub routes: HashMap<CurrentPage, RouteConfig>,
     pub config_options: ConfigOptions,
+    pub inventory_sync: Option<Receiver<JobEvent>>,
+    pub role_prefetch_done: usize,
+    pub last_synced: Option<DateTime<Utc>>,
+    /// Whether the rows come from a cache older than `inventory_ttl_minutes`.
+    pub inventory_stale: bool,
+    pub sync_error: Option<String>,
+    pub sync_throttled: bool,
 }
 
 impl Default for App {
@@ -77,6 +174,14 @@ impl Default for App {
         App {
             table_state: TableState::default(),
             rows: vec![],
+            visible_rows: vec![],
+            search_query: String::new(),
+            searching: false,
+            arn_input: String::new(),
+            entering_arn: false,
+            mfa_code_input: String::new(),
+            entering_mfa_code: false,
+            show_email: false,
             exit: false,
             scroll_state: ScrollbarState::default(),
             selected_account: AccountRow::default(),
@@ -85,52 +190,73 @@ impl Default for App {
             role_is_selected: false,
             selected_role: String::new(),
             role_credentials: RoleCredentials::default(),
+            credentials_error: None,
+            roles_error: None,
+            pending_confirmation: None,
+            export_format: "shell".to_string(),
+            format_picker_open: false,
+            format_table_state: TableState::default(),
+            console_picker_open: false,
+            console_table_state: TableState::default(),
+            clipboard_clear: None,
+            secrets_revealed_until: None,
+            presentation_mode: false,
+            credential_cache: RoleCredentialsCache::default(),
             credential_message: String::new(),
             aws_config_provider: ConfigProvider::default(),
             config_table_state: TableState::default(),
             value_input: String::new(),
             currently_editing: false,
+            metadata: MetadataStore::default(),
+            editing_metadata: false,
+            metadata_form: vec![],
+            metadata_table_state: TableState::default(),
+            pruning: false,
+            prune_profiles: vec![],
+            prune_table_state: TableState::default(),
+            review: None,
             token_prompt: String::new(),
             current_page: CurrentPage::AccountList,
             routes: HashMap::new(),
             config_options: ConfigOptions {
                 options: vec![],
             },
+            inventory_sync: None,
+            role_prefetch_done: 0,
+            last_synced: None,
+            inventory_stale: false,
+            sync_error: None,
+            sync_throttled: false,
         }
     }
 }
 
 impl App {    
-    pub fn load_config(&mut self) -> Result<Ini, Error> {
+    pub fn load_config(&mut self) -> Result<IniFile, Error> {
         let file_path = UserDirs::new().unwrap().home_dir().join(".assumer").join("config.ini");
     
-        let mut config = Ini::new();
         if !file_path.exists() {
-            let _ = std::fs::create_dir_all(file_path.parent().unwrap());
-            let _ = std::fs::write(file_path.clone(), "".as_bytes());
-            
+            let mut config = IniFile::parse("");
             self.config_options.options.iter().for_each(|option| {
-                config.with_section(Some("Main".to_string()))
-                    .set(option.name.clone(), option.value.clone());                
+                config.set("Main", &option.name, &option.value);
             });
     
             self.update_config(&mut config)?;
-        } else {
-            config = Ini::load_from_file(file_path.clone())?;
+            return Ok(config);
         }
     
-        Ok(config)
+        IniFile::load(&file_path).map_err(|err| eyre!("{:#}", err))
     }
     
-    pub fn update_config(&mut self, config: &mut Ini) -> Result<(), Error> {
+    pub fn update_config(&mut self, config: &mut IniFile) -> Result<(), Error> {
         let file_path = UserDirs::new().unwrap().home_dir().join(".assumer").join("config.ini");
-        config.write_to_file(file_path)?;
+        config.write_to_file(&file_path).map_err(|err| eyre!("{:#}", err))?;
         Ok(())
     }
 
-    /// runs the application's main loop until the user quits
-    pub fn run(&mut self, terminal: &mut tui::Tui) -> Result<()> {   
-        self.routes = self.create_routes();
+    /// Sets up the known config options with their defaults and overlays
+    /// whatever is saved in `~/.assumer/config.ini`.
+    pub fn init_config(&mut self) -> Result<()> {
         self.config_options = ConfigOptions {
             options: vec![
                 ConfigOption {
@@ -145,75 +271,370 @@ impl App {
                     name: "region".to_string(),
                     value: "us-east-1".to_string(),
                 },
+                ConfigOption {
+                    name: "sso_region".to_string(),
+                    value: "".to_string(),
+                },
+                ConfigOption {
+                    name: "show_account_email".to_string(),
+                    value: "false".to_string(),
+                },
+                ConfigOption {
+                    name: "role_prefetch_concurrency".to_string(),
+                    value: "8".to_string(),
+                },
+                ConfigOption {
+                    name: "inventory_ttl_minutes".to_string(),
+                    value: "60".to_string(),
+                },
+                ConfigOption {
+                    name: "retry_max_attempts".to_string(),
+                    value: "5".to_string(),
+                },
+                ConfigOption {
+                    name: "retry_max_backoff_seconds".to_string(),
+                    value: "20".to_string(),
+                },
+                ConfigOption {
+                    name: "sso_endpoint_url".to_string(),
+                    value: "".to_string(),
+                },
+                ConfigOption {
+                    name: "oidc_endpoint_url".to_string(),
+                    value: "".to_string(),
+                },
+                ConfigOption {
+                    name: "sts_endpoint_url".to_string(),
+                    value: "".to_string(),
+                },
+                ConfigOption {
+                    name: "federation_endpoint_url".to_string(),
+                    value: "".to_string(),
+                },
+                ConfigOption {
+                    name: "credential_cache_margin_minutes".to_string(),
+                    value: "10".to_string(),
+                },
+                ConfigOption {
+                    name: "cache_credentials_on_disk".to_string(),
+                    value: "false".to_string(),
+                },
+                ConfigOption {
+                    name: "expiry_warning_minutes".to_string(),
+                    value: "15".to_string(),
+                },
+                ConfigOption {
+                    name: "credentials_profile".to_string(),
+                    value: "default".to_string(),
+                },
+                ConfigOption {
+                    name: "profile_format".to_string(),
+                    value: "sso-session".to_string(),
+                },
+                ConfigOption {
+                    name: "profile_name_template".to_string(),
+                    value: profiles::DEFAULT_PROFILE_TEMPLATE.to_string(),
+                },
+                ConfigOption {
+                    name: "profile_name_case".to_string(),
+                    value: "keep".to_string(),
+                },
+                ConfigOption {
+                    name: "profile_name_replacement".to_string(),
+                    value: "-".to_string(),
+                },
+                ConfigOption {
+                    name: "export_format".to_string(),
+                    value: "shell".to_string(),
+                },
+                ConfigOption {
+                    name: "export_directory".to_string(),
+                    value: ".".to_string(),
+                },
+                ConfigOption {
+                    name: "clipboard".to_string(),
+                    value: "auto".to_string(),
+                },
+                ConfigOption {
+                    name: "console_destination".to_string(),
+                    value: "".to_string(),
+                },
+                ConfigOption {
+                    name: "browser".to_string(),
+                    value: "granted".to_string(),
+                },
+                ConfigOption {
+                    name: "browser_command".to_string(),
+                    value: "".to_string(),
+                },
+                ConfigOption {
+                    name: "console_session_seconds".to_string(),
+                    value: "".to_string(),
+                },
+                ConfigOption {
+                    name: "console_issuer".to_string(),
+                    value: "".to_string(),
+                },
+                ConfigOption {
+                    name: "console_roles".to_string(),
+                    value: "ReadOnlyAccess, ViewOnlyAccess".to_string(),
+                },
+                ConfigOption {
+                    name: "reveal_seconds".to_string(),
+                    value: "15".to_string(),
+                },
+                ConfigOption {
+                    name: "presentation_mode".to_string(),
+                    value: "false".to_string(),
+                },
+                ConfigOption {
+                    name: "clipboard_clear_seconds".to_string(),
+                    value: "30".to_string(),
+                },
             ],
         };
         let config = self.load_config()?;
-        
 
         // Map values from config to config_options
         for option in self.config_options.options.iter_mut() {
-            let section = config.section(Some("Main".to_string())).unwrap();
-            option.value = match section.get(&option.name) {
+            option.value = match config.get("Main", &option.name) {
                 Some(value) => value.to_string(),
                 None => option.value.clone(),                
             }
-        }      
-        self.load_aws_config(Some(false));      
+        }
+
+        self.reset_credential_cache();
+        self.metadata = MetadataStore::load(&sso::get_assumer_path().join("accounts.json"))
+            .map_err(|err| eyre!("Failed to load account metadata: {:#}", err))?;
+        Ok(())
+    }
 
+    /// runs the application's main loop until the user quits
+    pub fn run(&mut self, terminal: &mut tui::Tui) -> Result<()> {   
+        self.routes = self.create_routes();
+        self.init_config()?;
+        self.show_email = self.config_options.value("show_account_email") == "true";
+        self.export_format = self.config_options.value("export_format");
+        self.presentation_mode = self.config_options.value("presentation_mode") == "true";
+
+        // Draw whatever we have cached before talking to AWS at all, then
+        // refresh it in the background
+        self.load_cached_inventory();
+        terminal.draw(|frame| self.render_frame(frame))?;
+
+        self.load_aws_config(Some(false));      
         self.get_account_list();
                       
         while !self.exit {
+            self.poll_jobs();
+            self.clear_clipboard_if_due();
             terminal.draw(|frame| self.render_frame(frame))?;
             self.handle_events().wrap_err("handle events failed")?;
         }
+
+        // Leave the pending clear to a detached process
+        if let Some((clear_at, digest, backend)) = self.clipboard_clear.take() {
+            let _ = clipboard::clear_later(&digest, backend, clear_at.saturating_duration_since(Instant::now()));
+        }
         Ok(())
     }
 
     pub fn load_aws_config(&mut self, new_token: Option<bool>) {
         let start_url = self.config_options.options.iter().find(|option| option.name == "start_url").unwrap().value.clone();
-        let region = self.config_options.options.iter().find(|option| option.name == "region").unwrap().value.clone();
+        let region = sso::sso_region(&self.config_options);
 
-        self.aws_config_provider = match sso::get_aws_config(start_url.as_str(), region.as_str(), self, Some(new_token.unwrap_or(false))) {
-            Ok(access_token) => access_token,
-            Err(_) => ConfigProvider::default(),
-        };
+        self.aws_config_provider = sso::get_aws_config(start_url.as_str(), region.as_str(), self, Some(new_token.unwrap_or(false))).unwrap_or_default();
     }
 
+    /// Starts a background sync of the account and role inventory. The rows
+    /// already on screen, cached or not, stay put until the new list arrives.
     pub fn get_account_list(&mut self) {
-        if !self.aws_config_provider.account_info_provider.is_none() {
-            let sso_accounts = sso::get_sso_accounts(self);
+        self.inventory_sync = None;
+        self.sync_error = None;
+        self.sync_throttled = false;
+        if self.aws_config_provider.account_info_provider.is_some() {
+            let config_provider = self.aws_config_provider.clone();
+            let concurrency = self.config_options.value("role_prefetch_concurrency").parse().unwrap_or(8);
+
+            self.role_prefetch_done = 0;
+            self.inventory_sync = Some(jobs::spawn(move |sender| {
+                sso::sync_inventory(config_provider, concurrency, sender);
+            }));
+        } else {
+            self.show_sync_error(anyhow::anyhow!("No AWS Config Provider"));
+        }
+    }
+
+    /// Replaces the rows with the cached inventory for the configured start
+    /// URL, if there is one, and marks them stale when the cache is older
+    /// than its TTL.
+    pub fn load_cached_inventory(&mut self) {
+        let Ok(inventory) = self.inventory_cache().get_cached_inventory() else {
             self.rows = vec![];
-            match sso_accounts {
-                Ok(sso_accounts) => {          
-                    for account in sso_accounts {
-                        self.rows.push(AccountRow {
-                            account_name: account.account_name,
-                            account_id: account.account_id,
-                            roles: account.roles,
-                        });
-                    } 
+            self.last_synced = None;
+            self.inventory_stale = false;
+            self.apply_filter();
+            return;
+        };
+        self.inventory_stale = !inventory.is_fresh(self.inventory_ttl());
+
+        self.rows = inventory.accounts.into_iter().map(AccountRow::from).collect();
+        self.last_synced = Some(inventory.synced_at);
+        self.apply_filter();
+    }
+
+    /// Starts a fresh role credentials cache for the current start URL,
+    /// dropping whatever was cached in memory for the previous config.
+    pub fn reset_credential_cache(&mut self) {
+        let start_url = self.config_options.value("start_url");
+        let margin = self.config_options.value("credential_cache_margin_minutes").parse().unwrap_or(10);
+        let cache_dir = sso::get_assumer_path().join("cache");
+        let on_disk = self.config_options.value("cache_credentials_on_disk") == "true";
+        self.credential_cache = RoleCredentialsCache::new(
+            &start_url,
+            on_disk.then_some(cache_dir.as_path()),
+            chrono::Duration::minutes(margin),
+        );
+    }
+
+    /// How long a cached inventory counts as up to date.
+    pub fn inventory_ttl(&self) -> chrono::Duration {
+        chrono::Duration::minutes(self.config_options.value("inventory_ttl_minutes").parse().unwrap_or(60))
+    }
+
+    pub fn inventory_cache(&self) -> InventoryCache {
+        let start_url = self.config_options.value("start_url");
+        InventoryCache::new(&start_url, &sso::get_assumer_path().join("cache"))
+    }
+
+    fn save_inventory(&mut self) {
+        let accounts = self.rows.iter().map(AccountRow::account_info).collect();
+
+        match self.inventory_cache().cache_inventory(accounts) {
+            Ok(inventory) => {
+                self.last_synced = Some(inventory.synced_at);
+                self.inventory_stale = false;
+            }
+            Err(err) => self.sync_error = Some(err.to_string()),
+        }
+    }
+
+    fn show_sync_error(&mut self, error: anyhow::Error) {
+        let throttled = aws::is_throttled(&error);
+        if self.rows.is_empty() || self.rows.iter().all(|row| row.account_name == "Error" || row.account_name == "Throttled") {
+            self.rows = vec![AccountRow {
+                account_name: if throttled { "Throttled" } else { "Error" }.to_string(),
+                account_id: error.to_string(),
+                throttled,
+                ..AccountRow::default()
+            }];
+            self.apply_filter();
+        }
+        self.sync_error = Some(error.to_string());
+        self.sync_throttled = throttled;
+    }
+
+    /// Applies whatever background jobs have reported since the last frame.
+    fn poll_jobs(&mut self) {
+        let Some(receiver) = &self.inventory_sync else { return };
+        let events = receiver.try_iter().collect::<Vec<_>>();
+
+        for event in events {
+            match event {
+                JobEvent::Accounts(Ok(accounts)) => {
+                    // keep the cached roles until fresh ones come in
+                    let previous_rows = std::mem::take(&mut self.rows);
+                    self.rows = accounts.into_iter()
+                        .map(|account| {
+                            let mut row = AccountRow::from(account);
+                            if let Some(previous) = previous_rows.iter().find(|previous| previous.account_id == row.account_id) {
+                                row.roles = previous.roles.clone();
+                            }
+                            row
+                        })
+                        .collect();
+                    self.apply_filter();
                 }
-                Err(err) => {
-                    self.rows.push(AccountRow {
-                        account_name: "Error".to_string(),
-                        account_id: err.to_string(),
-                        roles: vec![],
-                    });
+                JobEvent::Accounts(Err(err)) => {
+                    self.inventory_sync = None;
+                    self.show_sync_error(err);
                 }
-            }  
-        } else {
-            self.rows = vec![];
-            self.rows.push(AccountRow {
-                account_name: "Error".to_string(),
-                account_id: "No AWS Config Provider".to_string(),
-                roles: vec![],
-            });
+                JobEvent::AccountRoles { account_id, roles } => {
+                    self.role_prefetch_done += 1;
+                    let row = self.rows.iter_mut().find(|row| row.account_id == account_id);
+                    let roles = match (roles, row) {
+                        (Ok(roles), Some(row)) => {
+                            row.roles = roles.clone();
+                            row.throttled = false;
+                            roles
+                        }
+                        (Err(err), Some(row)) => {
+                            row.throttled = aws::is_throttled(&err);
+                            continue;
+                        }
+                        (Ok(roles), None) => roles,
+                        (Err(_), None) => continue,
+                    };
+                    if self.selected_account.account_id == account_id && !self.is_selected {
+                        self.selected_account.roles = roles;
+                    }
+                }
+                JobEvent::InventorySyncFinished => {
+                    self.inventory_sync = None;
+                    self.save_inventory();
+                }
+            }
+        }
+
+        if !self.search_query.is_empty() {
+            self.apply_filter();
         }
     }
 
+    /// Whether an inventory sync is still running, and how many accounts
+    /// have had their roles fetched so far.
+    pub fn role_prefetch_progress(&self) -> Option<(usize, usize)> {
+        self.inventory_sync.as_ref().map(|_| (self.role_prefetch_done, self.rows.len()))
+    }
+
+    /// Recomputes which rows match the search query and keeps the
+    /// selection on the same account where possible.
+    pub fn apply_filter(&mut self) {
+        let selected_id = self.table_state.selected()
+            .and_then(|i| self.visible_rows.get(i))
+            .and_then(|&row| self.rows.get(row))
+            .map(|row| row.account_id.clone());
+
+        self.visible_rows = self.rows.iter().enumerate()
+            .filter(|(_, row)| {
+                self.search_query.is_empty()
+                    || row.matches(&self.search_query)
+                    || self.metadata.get(&row.account_id).is_some_and(|metadata| metadata.matches(&self.search_query))
+            })
+            .map(|(i, _)| i)
+            .collect();
+
+        let selected = selected_id
+            .and_then(|id| self.visible_rows.iter().position(|&row| self.rows[row].account_id == id))
+            .or(if self.visible_rows.is_empty() { None } else { Some(0) });
+
+        self.table_state.select(selected);
+        self.selected_account = match selected {
+            Some(i) => self.rows[self.visible_rows[i]].clone(),
+            None => AccountRow::default(),
+        };
+        self.scroll_state = self.scroll_state.position(selected.unwrap_or(0) * ITEM_HEIGHT);
+    }
+
     fn render_frame(&mut self, frame: &mut Frame) {        
-        if self.currently_editing {
+        if self.review.is_some() {
+            self.route(frame, CurrentPage::Review);
+        } else if self.currently_editing {
             self.route(frame, CurrentPage::Config);
+        } else if self.editing_metadata {
+            self.route(frame, CurrentPage::Metadata);
+        } else if self.pruning {
+            self.route(frame, CurrentPage::Prune);
         } else if self.role_is_selected {
             self.route(frame,CurrentPage::Credentials);
         } else if self.is_selected {
@@ -237,29 +658,47 @@ impl App {
         // Config route
         routes.insert(CurrentPage::Config, RouteConfig {
             layout: |frame| widgets::config::get_layout(frame),
-            render: |frame, mut app, rect| widgets::render_config(frame, &mut app, rect),
+            render: |frame, app, rect| widgets::render_config(frame, app, rect),
         });
 
         // Credentials route
         routes.insert(CurrentPage::Credentials, RouteConfig {
             layout: |frame| widgets::credentials::get_layout(frame),
-            render: |frame, mut app, rect| widgets::render_credentials(frame, &mut app, rect),
+            render: |frame, app, rect| widgets::render_credentials(frame, app, rect),
+        });
+
+        // Metadata route
+        routes.insert(CurrentPage::Metadata, RouteConfig {
+            layout: |frame| widgets::metadata::get_layout(frame),
+            render: |frame, app, rect| widgets::render_metadata(frame, app, rect),
+        });
+
+        // Prune route
+        routes.insert(CurrentPage::Prune, RouteConfig {
+            layout: |frame| widgets::prune::get_layout(frame),
+            render: |frame, app, rect| widgets::render_prune(frame, app, rect),
+        });
+
+        // Review route
+        routes.insert(CurrentPage::Review, RouteConfig {
+            layout: |frame| widgets::review::get_layout(frame),
+            render: |frame, app, rect| widgets::render_review(frame, app, rect),
         });
 
         // AccountList route
         routes.insert(CurrentPage::AccountList, RouteConfig {
             layout: |frame| widgets::accounts::get_layout(frame),
-            render: |frame, mut app, rect| widgets::render_accounts(frame, &mut app, rect),
+            render: |frame, app, rect| widgets::render_accounts(frame, app, rect),
         });
 
         // Roles route
         routes.insert(CurrentPage::Roles, RouteConfig {
             layout: |frame| widgets::roles::get_layout(frame),
-            render: |frame, mut app, rect| {
-                widgets::render_accounts(frame, &mut app, rect);
+            render: |frame, app, rect| {
+                widgets::render_accounts(frame, app, rect);
                 if app.is_selected {
                     let rects = widgets::roles::get_layout(frame);
-                    widgets::render_roles(frame, &mut app, rects[1]);
+                    widgets::render_roles(frame, app, rects[1]);
                 }
             },
         });
@@ -269,6 +708,11 @@ impl App {
 
     /// updates the application's self based on user input
     fn handle_events(&mut self) -> Result<()> {
+        // poll with a timeout so background job results get drawn even
+        // while the user isn't pressing anything
+        if !event::poll(TICK_RATE)? {
+            return Ok(());
+        }
         match event::read()? {
             // it's important to check that the event is a key press event as
             // crossterm also emits key release and repeat events on Windows.
@@ -294,88 +738,574 @@ impl App {
             CurrentPage::Config => {
                 let _ = widgets::config::handle_key_events(self, key_event);
             }
+            CurrentPage::Metadata => {
+                let _ = widgets::metadata::handle_key_events(self, key_event);
+            }
+            CurrentPage::Prune => {
+                let _ = widgets::prune::handle_key_events(self, key_event);
+            }
+            CurrentPage::Review => {
+                let _ = widgets::review::handle_key_events(self, key_event);
+            }
         }
 
         Ok(())
     }
 
+    /// Opens the console at the destination configured for the role.
     pub fn open_console(&mut self) {
-        if self.role_is_selected {
-            let account_info = AccountInfo {
-                account_name: self.selected_account.account_name.clone(),
-                account_id: self.selected_account.account_id.clone(),
-                roles: vec![],
+        let destination = console::destination(&self.config_options, &self.metadata, &self.selected_account.account_id, &self.selected_role);
+        self.open_console_at(&destination);
+    }
+
+    /// Opens the console at one of the common services, in the role's
+    /// working region.
+    pub fn open_console_service(&mut self, service: &console::Service) {
+        let region = sso::working_region(&self.config_options, &self.metadata, &self.selected_account.account_id, &self.selected_role);
+        self.open_console_at(&console::destination_url(service.path, &region, sso::partition(&self.config_options)));
+    }
+
+    /// Opens the console at the resource in `arn_input`, signed in to the
+    /// ARN's account (the selected one for ARNs without an account) with
+    /// the preferred role, and shows that role's credentials.
+    pub fn open_arn(&mut self) {
+        let arn = match console::Arn::parse(&self.arn_input).and_then(|arn| {
+            arn.check_partition(sso::partition(&self.config_options))?;
+            Ok(arn)
+        }) {
+            Ok(arn) => arn,
+            Err(err) => {
+                self.credential_message = err.to_string();
+                return;
+            }
+        };
+        let account_id = if arn.account_id.is_empty() { self.selected_account.account_id.clone() } else { arn.account_id.clone() };
+        let Some(row) = self.rows.iter().find(|row| row.account_id == account_id).cloned() else {
+            self.credential_message = format!("You have no SSO access to account {}", self.display_account_id(&account_id));
+            return;
+        };
+        self.selected_account = row;
+        self.select_account();
+        let Some(role) = console::preferred_role(&self.selected_account.roles, &self.config_options) else {
+            self.credential_message = match &self.roles_error {
+                Some(err) => format!("Could not list your roles in account {}: {}", self.display_account_id(&account_id), err),
+                None => format!("You have no roles in account {}", self.display_account_id(&account_id)),
             };
-            let _ = sso::open_console(self.role_credentials.clone(), account_info, &self.selected_role);
-            self.credential_message += "Done!";
+            return;
+        };
+        self.role_table_state.select(self.selected_account.roles.iter().position(|name| *name == role));
+        self.selected_role = role;
+        self.select_role();
+        self.current_page = CurrentPage::Credentials;
+        if self.credentials_error.is_none() {
+            let region = sso::working_region(&self.config_options, &self.metadata, &self.selected_account.account_id, &self.selected_role);
+            self.credential_message = format!("Opening {}... ", arn);
+            self.open_console_at(&console::arn_url(&arn, &region));
+        }
+    }
+
+    fn open_console_at(&mut self, destination: &str) {
+        if self.role_is_selected {
+            let account_info = self.selected_account.account_info();
+            let federation_endpoint = sso::federation_endpoint(&self.config_options);
+            let opened = console::session(&self.config_options, &self.metadata, &account_info.account_id, &self.selected_role).and_then(|session| {
+                let launcher = browser::from_config(&self.config_options)?;
+                sso::open_console(self.role_credentials.clone(), account_info, &self.selected_role, &federation_endpoint, destination, &session, launcher.as_ref())
+            });
+            match opened {
+                Ok(()) => self.credential_message += "Done!",
+                Err(err) => self.credential_message = format!("Could not open the console: {:#}", err),
+            }
         }
-        ()
     }
 
     pub fn exit(&mut self) {
         self.exit = true;
     }
 
-    pub fn export(&mut self) {
-        #[cfg(target_os = "windows")]
-        {
-            self.credential_message = "Setting environment variables for AWS CLI - Windows...".to_string();
+    /// Shows secrets on the credentials page for `reveal_seconds`, or hides
+    /// them again right away if they are showing.
+    pub fn toggle_secrets(&mut self) {
+        if self.secrets_revealed() {
+            self.secrets_revealed_until = None;
+        } else {
+            let seconds = self.config_options.value("reveal_seconds").parse().unwrap_or(15);
+            self.secrets_revealed_until = Some(Instant::now() + Duration::from_secs(seconds));
         }
+    }
+
+    pub fn secrets_revealed(&self) -> bool {
+        self.secrets_revealed_until.is_some_and(|until| until > Instant::now())
+    }
 
-        #[cfg(target_os = "macos")]
-        {
-            self.credential_message = "Copied environment variable exports for AWS CLI - MacOS...".to_string();
+    /// How to show a secret on screen: masked unless revealed.
+    pub fn display_secret(&self, value: &str) -> String {
+        if self.secrets_revealed() {
+            value.to_string()
+        } else {
+            mask::mask_secret(value, 4)
+        }
+    }
+
+    /// How to show an account ID on screen: masked in presentation mode.
+    pub fn display_account_id(&self, account_id: &str) -> String {
+        if self.presentation_mode {
+            mask::mask_account_id(account_id)
+        } else {
+            account_id.to_string()
         }
+    }
 
-        #[cfg(target_os = "linux")]
-        {
-            self.credential_message = "Copied environment variable exports for AWS CLI - Linux...".to_string();
+    /// How to show text that may contain account IDs, such as profile
+    /// names and diffs: with every ID masked in presentation mode.
+    pub fn display_account_ids(&self, text: &str) -> String {
+        if self.presentation_mode {
+            mask::mask_account_ids(text)
+        } else {
+            text.to_string()
         }
+    }
+
+    /// The exporter picked in the credentials page, falling back to shell
+    /// exports if the configured name is unknown.
+    pub fn exporter(&self) -> Box<dyn Exporter> {
+        exporters::find(&self.export_format).unwrap_or_else(|| Box::new(exporters::Shell))
+    }
 
+    pub fn export_context(&self) -> ExportContext {
+        ExportContext {
+            account_id: self.selected_account.account_id.clone(),
+            account_name: self.selected_account.account_name.clone(),
+            role: self.role_credentials.name.clone(),
+            region: sso::working_region(&self.config_options, &self.metadata, &self.selected_account.account_id, &self.role_credentials.name),
+        }
+    }
+
+    /// Puts the credentials on the clipboard in the selected format.
+    pub fn copy_credentials(&mut self) {
+        if self.credentials_error.is_some() || !self.confirm_expiry('y') {
+            return;
+        }
+        let exporter = self.exporter();
+        let text = exporter.export(&self.role_credentials, &self.export_context());
+        self.copy_text(exporter.name(), &text);
+    }
+
+    /// Copies a console sign-in URL for the role's console destination.
+    pub fn copy_console_url(&mut self) {
+        if self.credentials_error.is_some() || !self.confirm_expiry('u') {
+            return;
+        }
+        let destination = console::destination(&self.config_options, &self.metadata, &self.selected_account.account_id, &self.selected_role);
+        let federation_endpoint = sso::federation_endpoint(&self.config_options);
+        let url = console::session(&self.config_options, &self.metadata, &self.selected_account.account_id, &self.selected_role)
+            .and_then(|session| sso::console_url(&self.role_credentials, &federation_endpoint, &destination, &session));
+        match url {
+            Ok(url) => self.copy_text("the console sign-in URL (valid for 15 minutes)", &url),
+            Err(err) => self.credential_message = format!("Could not get a console sign-in URL: {:#}", err),
+        }
+    }
+
+    /// Puts `text` on the clipboard, and schedules clearing it again if
+    /// configured.
+    fn copy_text(&mut self, what: &str, text: &str) {
+        let backend = clipboard::Backend::from_config(&self.config_options.value("clipboard"));
+        self.credential_message = match clipboard::copy(text, backend) {
+            Ok(used) => {
+                let clear_after = self.clipboard_clear_after();
+                let via = if used == clipboard::Backend::Osc52 { " via OSC 52" } else { "" };
+                match clear_after {
+                    Some(delay) => {
+                        self.clipboard_clear = Some((Instant::now() + delay, clipboard::digest(text), used));
+                        format!("Copied {}{}, clearing in {}s", what, via, delay.as_secs())
+                    }
+                    None => format!("Copied {}{}", what, via),
+                }
+            }
+            Err(err) => format!("Could not copy: {:#}", err),
+        };
+    }
+
+    /// Writes the credentials in the selected format to `export_directory`.
+    pub fn save_credentials(&mut self) {
+        if self.credentials_error.is_some() || !self.confirm_expiry('w') {
+            return;
+        }
+        let exporter = self.exporter();
+        let text = exporter.export(&self.role_credentials, &self.export_context());
+        let path = exporters::default_path(exporter.as_ref(), &PathBuf::from(self.config_options.value("export_directory")));
+        self.credential_message = match exporters::write_to_file(exporter.as_ref(), &text, &path) {
+            Ok(()) => format!("Wrote {} to {}", exporter.name(), path.display()),
+            Err(err) => format!("Could not write {}: {:#}", path.display(), err),
+        };
+    }
+
+    fn clipboard_clear_after(&self) -> Option<Duration> {
+        match self.config_options.value("clipboard_clear_seconds").parse().unwrap_or(30) {
+            0 => None,
+            seconds => Some(Duration::from_secs(seconds)),
+        }
+    }
+
+    fn clear_clipboard_if_due(&mut self) {
+        if self.clipboard_clear.as_ref().is_some_and(|(clear_at, _, _)| *clear_at <= Instant::now()) {
+            let (_, digest, backend) = self.clipboard_clear.take().unwrap();
+            let _ = clipboard::clear(&digest, backend);
+        }
+    }
+
+    /// How close to expiry credentials have to be before we warn about them.
+    pub fn expiry_warning(&self) -> chrono::Duration {
+        chrono::Duration::minutes(self.config_options.value("expiry_warning_minutes").parse().unwrap_or(15))
+    }
+
+    /// Whether the action on `key` may go ahead. With `warnings`, the first
+    /// press only shows them and the action needs the same key pressed
+    /// again.
+    fn confirm(&mut self, key: char, warnings: Vec<String>) -> bool {
+        if warnings.is_empty() || self.pending_confirmation == Some(key) {
+            self.pending_confirmation = None;
+            return true;
+        }
+        self.pending_confirmation = Some(key);
+        self.credential_message = format!("{}, press <{}> again to go ahead anyway", warnings.join(" and "), key.to_ascii_uppercase());
+        false
+    }
+
+    fn expiry_warnings(&self) -> Vec<String> {
+        if self.role_credentials.expires_within(self.expiry_warning()) {
+            vec![format!("Credentials are close to expiry ({})", self.role_credentials.expiry_countdown())]
+        } else {
+            vec![]
+        }
+    }
+
+    /// [App::confirm] for using credentials that may be close to expiry.
+    fn confirm_expiry(&mut self, key: char) -> bool {
+        let warnings = self.expiry_warnings();
+        self.confirm(key, warnings)
+    }
+
+    /// Writes the credentials to the `credentials_profile` profile, asking
+    /// first when they are close to expiry or the profile holds settings
+    /// assumer didn't write.
+    pub fn export(&mut self) {
+        if self.credentials_error.is_some() {
+            return;
+        }
+
+        let account_info = self.selected_account.account_info();
+        let alias = self.metadata.get(&account_info.account_id).map(|metadata| metadata.alias.clone()).unwrap_or_default();
+        let profile = profiles::ProfileNaming::from_config(&self.config_options, "credentials_profile", "default")
+            .name(&account_info, &self.role_credentials.name, &alias);
         let aws_config_path = self.config_options.options.iter().find(|option| option.name == "aws_config_path").unwrap().clone();
-        let _ = sso::export_env_vars(&self.role_credentials, aws_config_path);
-        self.credential_message += "Done!";
+        let mut warnings = self.expiry_warnings();
+        let credentials_file = CredentialsFile::new(&PathBuf::from(&aws_config_path.value).join("credentials"));
+        if credentials_file.is_unmanaged(&profile).unwrap_or(false) {
+            warnings.push(format!("[{}] wasn't written by assumer and would be replaced", profile));
+        }
+        if !self.confirm('e', warnings) {
+            return;
+        }
+        self.credential_message = match sso::export_env_vars(&self.role_credentials, &account_info, aws_config_path, &profile, true) {
+            Ok(()) => format!("Wrote credentials to the [{}] profile", profile),
+            Err(err) => format!("Could not write credentials: {:#}", err),
+        };
     }
 
     pub fn select_account(&mut self) {
+        if self.selected_account.account_id.is_empty() {
+            return;
+        }
         self.is_selected = true;
-        let account_info = AccountInfo {
-            account_name: self.selected_account.account_name.clone(),
-            account_id: self.selected_account.account_id.clone(),
-            roles: vec![],
+        self.roles_error = None;
+        if self.selected_account.roles.is_empty() {
+            let account_info = self.selected_account.account_info();
+            let roles = match sso::get_account_roles(self, account_info) {
+                Ok(roles) => {
+                    if let Some(row) = self.rows.iter_mut().find(|row| row.account_id == self.selected_account.account_id) {
+                        row.roles = roles.clone();
+                    }
+                    roles
+                }
+                Err(err) => {
+                    self.roles_error = Some(err.to_string());
+                    vec![]
+                }
+            };
+            self.selected_account.roles = roles;
+        }
+        let chained_roles = self.metadata.get(&self.selected_account.account_id)
+            .map(|metadata| metadata.chained_roles.clone())
+            .unwrap_or_default();
+        for chained in chained_roles {
+            let label = chained.label();
+            if !self.selected_account.roles.contains(&label) {
+                self.selected_account.roles.push(label);
+            }
+        }
+        // An account without roles shows an empty list with nothing selected
+        self.selected_role = self.selected_account.roles.first().cloned().unwrap_or_default();
+        self.role_table_state.select(if self.selected_account.roles.is_empty() { None } else { Some(0) });
+    }
+
+    /// Opens the details editor for the selected account.
+    pub fn edit_metadata(&mut self) {
+        if self.selected_account.account_id.is_empty() {
+            return;
+        }
+        let metadata = self.metadata.get(&self.selected_account.account_id).cloned().unwrap_or_default();
+        self.metadata_form = vec![
+            ConfigOption {
+                name: "Alias".to_string(),
+                value: metadata.alias,
+            },
+            ConfigOption {
+                name: "Tags".to_string(),
+                value: metadata.tags.join(", "),
+            },
+            ConfigOption {
+                name: "Note".to_string(),
+                value: metadata.note,
+            },
+            ConfigOption {
+                name: "Region".to_string(),
+                value: metadata.region,
+            },
+            ConfigOption {
+                name: "Role regions".to_string(),
+                value: format_role_settings(&metadata.role_regions),
+            },
+            ConfigOption {
+                name: "Console".to_string(),
+                value: metadata.console_destination,
+            },
+            ConfigOption {
+                name: "Role consoles".to_string(),
+                value: format_role_settings(&metadata.role_console_destinations),
+            },
+            ConfigOption {
+                name: "Session secs".to_string(),
+                value: metadata.console_session_seconds.map(|seconds| seconds.to_string()).unwrap_or_default(),
+            },
+            ConfigOption {
+                name: "Role sessions".to_string(),
+                value: format_role_settings(&metadata.role_console_session_seconds),
+            },
+            ConfigOption {
+                name: "Issuer".to_string(),
+                value: metadata.console_issuer,
+            },
+            ConfigOption {
+                name: "Role issuers".to_string(),
+                value: format_role_settings(&metadata.role_console_issuers),
+            },
+        ];
+        self.metadata_table_state.select(Some(0));
+        self.editing_metadata = true;
+    }
+
+    pub fn save_metadata(&mut self) -> anyhow::Result<()> {
+        let field = |name: &str| self.metadata_form.iter()
+            .find(|option| option.name == name)
+            .map(|option| option.value.trim().to_string())
+            .unwrap_or_default();
+        let role_regions = parse_role_settings(&field("Role regions"), "role regions")?;
+        let role_console_destinations = parse_role_settings(&field("Role consoles"), "role consoles")?;
+        let console_session_seconds = match field("Session secs").as_str() {
+            "" => None,
+            seconds => Some(parse_seconds(seconds)?),
         };
-        let roles = match sso::get_account_roles(self, account_info) {
-            Ok(roles) => roles,
-            Err(err) => vec![err.to_string()],
+        let role_console_session_seconds = parse_role_settings(&field("Role sessions"), "role sessions")?
+            .into_iter()
+            .map(|(role, seconds)| Ok((role, parse_seconds(&seconds)?)))
+            .collect::<anyhow::Result<_>>()?;
+        let role_console_issuers = parse_role_settings(&field("Role issuers"), "role issuers")?;
+        let metadata = AccountMetadata {
+            alias: field("Alias"),
+            tags: field("Tags").split(',')
+                .map(|tag| tag.trim().to_string())
+                .filter(|tag| !tag.is_empty())
+                .collect(),
+            note: field("Note"),
+            region: field("Region"),
+            role_regions,
+            console_destination: field("Console"),
+            role_console_destinations,
+            console_session_seconds,
+            role_console_session_seconds,
+            console_issuer: field("Issuer"),
+            role_console_issuers,
+            ..self.metadata.get(&self.selected_account.account_id).cloned().unwrap_or_default()
         };
-        self.selected_account.roles = roles;        
-        self.selected_role = self.selected_account.roles[0].clone();
-        self.role_table_state.select(Some(0));
+        self.metadata.set(&self.selected_account.account_id, metadata)?;
+        self.editing_metadata = false;
+        self.apply_filter();
+        Ok(())
     }
 
-    pub fn select_role(&mut self) {
-        let account_info = AccountInfo {
-            account_name: self.selected_account.account_name.clone(),
-            account_id: self.selected_account.account_id.clone(),
-            roles: vec![],
+    /// Lists the assumer-owned profiles for the prune page, checked against
+    /// the accounts and roles loaded so far. Stale ones start out selected.
+    /// Refused until the account list has synced without errors, since
+    /// every profile would look stale against a partial one.
+    pub fn open_prune(&mut self) {
+        if self.inventory_sync.is_some() {
+            self.credential_message = "Wait for the roles to finish syncing".to_string();
+            return;
+        }
+        if self.sync_error.is_some() || self.rows.is_empty() {
+            self.credential_message = "Can't prune profiles without a synced account list, refresh with <R>".to_string();
+            return;
+        }
+        let aws_config_path = PathBuf::from(self.config_options.value("aws_config_path"));
+        let mut managed = match profiles::find_managed_profiles(&aws_config_path) {
+            Ok(managed) => managed,
+            Err(err) => {
+                self.credential_message = format!("Could not read profiles: {:#}", err);
+                vec![]
+            }
         };
+        let accounts: Vec<AccountInfo> = self.rows.iter().map(AccountRow::account_info).collect();
+        profiles::classify(&mut managed, &accounts, &self.metadata);
+        self.prune_profiles = managed.into_iter()
+            .map(|profile| {
+                let stale = profile.status.is_stale();
+                (profile, stale)
+            })
+            .collect();
+        self.prune_table_state.select(if self.prune_profiles.is_empty() { None } else { Some(0) });
+        self.pruning = true;
+    }
+
+    /// Works out the changes for the selected profiles so they can be shown
+    /// as a diff before anything is written.
+    pub fn preview_prune(&mut self) {
+        let aws_config_path = PathBuf::from(self.config_options.value("aws_config_path"));
+        let selected: Vec<&profiles::ManagedProfile> = self.prune_profiles.iter()
+            .filter(|(_, selected)| *selected)
+            .map(|(profile, _)| profile)
+            .collect();
+        if selected.is_empty() {
+            self.credential_message = "No profiles selected".to_string();
+            return;
+        }
+        let removed = selected.len();
+        match profiles::plan_removal(&aws_config_path, &selected) {
+            Ok(changes) => self.review = Some(Review {
+                title: format!("Remove {} profiles?", removed),
+                warnings: vec![],
+                changes,
+                done_message: format!("Removed {} profiles", removed),
+                scroll: 0,
+            }),
+            Err(err) => self.credential_message = format!("Could not read profiles: {:#}", err),
+        }
+    }
+
+    /// Plans a profile in `~/.aws/config` for every role of every account
+    /// and shows the diff for review. Needs the roles of every account, so
+    /// it waits for the inventory sync to finish.
+    pub fn sync_profiles(&mut self) {
+        if self.inventory_sync.is_some() {
+            self.credential_message = "Wait for the roles to finish syncing".to_string();
+            return;
+        }
+        let mut warnings = vec![];
+        let throttled = self.rows.iter().filter(|row| row.throttled).count();
+        if throttled > 0 {
+            warnings.push(format!("{} accounts are missing roles, refresh with <R> to include them", throttled));
+        }
+        let aws_config_path = PathBuf::from(self.config_options.value("aws_config_path"));
+        let accounts: Vec<AccountInfo> = self.rows.iter().map(AccountRow::account_info).collect();
+        match profiles::plan_sync(&aws_config_path, &accounts, &self.metadata, &self.config_options) {
+            Ok(sync) if sync.changes.is_empty() => {
+                self.credential_message = format!("All {} profiles are up to date", sync.profiles.len());
+            }
+            Ok(sync) => {
+                warnings.extend(sync.collisions);
+                self.review = Some(Review {
+                    title: format!("Write {} profiles?", sync.profiles.len()),
+                    warnings,
+                    changes: sync.changes,
+                    done_message: format!("Wrote {} profiles", sync.profiles.len()),
+                    scroll: 0,
+                });
+            }
+            Err(err) => self.credential_message = format!("Could not read profiles: {:#}", err),
+        }
+    }
+
+    /// Writes the changes under review.
+    pub fn apply_review(&mut self) {
+        let Some(review) = self.review.take() else { return };
+        match profiles::apply(&review.changes) {
+            Ok(()) => {
+                if self.pruning {
+                    self.open_prune();
+                }
+                self.credential_message = review.done_message;
+            }
+            Err(err) => self.credential_message = format!("Could not write profiles: {:#}", err),
+        }
+    }
+
+    pub fn select_role(&mut self) {
+        let account_info = self.selected_account.account_info();
         let role = self.selected_role.clone();        
-        let role_credentials = match sso::get_account_role_credentials(self, account_info.clone(), &role) {
-            Ok(role_credentials) => role_credentials,
-            Err(err) => sso::RoleCredentials {
-                name: "".to_string(),
-                access_key_id: "".to_string(),
-                secret_access_key: "".to_string(),
-                session_token: "".to_string(),
-                expiration: err.to_string(),
-            },
+        self.pending_confirmation = None;
+        self.secrets_revealed_until = None;
+        let chained = self.metadata.chained_role(&account_info.account_id, &role).cloned();
+        // Chained roles behind MFA ask for a token code first, unless their
+        // credentials are still cached
+        let needs_mfa_code = chained.as_ref().is_some_and(|chained| {
+            chained.mfa_serial.is_some() && self.credential_cache.get(&account_info.account_id, &chained.cache_key()).is_none()
+        });
+        if needs_mfa_code && !self.entering_mfa_code {
+            self.mfa_code_input.clear();
+            self.entering_mfa_code = true;
+            self.role_credentials = RoleCredentials {
+                name: role,
+                ..RoleCredentials::default()
+            };
+            self.credentials_error = None;
+            self.credential_message.clear();
+            self.role_is_selected = true;
+            return;
+        }
+        self.entering_mfa_code = false;
+        let mfa_code = std::mem::take(&mut self.mfa_code_input);
+        let role_credentials = match &chained {
+            Some(chained) => sso::get_chained_role_credentials(self, account_info.clone(), chained, needs_mfa_code.then_some(mfa_code.as_str())),
+            None => sso::get_account_role_credentials(self, account_info.clone(), &role),
         };
-        self.role_credentials = role_credentials;
+        match role_credentials {
+            Ok(role_credentials) => {
+                self.role_credentials = role_credentials;
+                self.credentials_error = None;
+            }
+            Err(err) => {
+                self.role_credentials = RoleCredentials {
+                    name: role.clone(),
+                    ..RoleCredentials::default()
+                };
+                self.credentials_error = Some(err.to_string());
+            }
+        }
         self.role_is_selected = true;
-        let _ = sso::export_profiles( account_info, &role, &self.config_options);
+        // Profiles can only describe SSO roles, and only ones that worked.
+        if chained.is_none() && self.credentials_error.is_none() {
+            let alias = self.metadata.get(&account_info.account_id).map(|metadata| metadata.alias.clone()).unwrap_or_default();
+            let region = sso::working_region(&self.config_options, &self.metadata, &account_info.account_id, &role);
+            if let Err(err) = sso::export_profiles(account_info, &role, &alias, &region, &self.config_options) {
+                self.credential_message = format!("Could not write the config profile: {:#}", err);
+            }
+        }
     }
 
     pub fn next_role(&mut self) {
+        if self.selected_account.roles.is_empty() {
+            return;
+        }
         let i = match self.role_table_state.selected() {
             Some(i) => {
                 if i >= self.selected_account.roles.len() - 1 {
@@ -392,6 +1322,9 @@ impl App {
     }
 
     pub fn previous_role(&mut self) {
+        if self.selected_account.roles.is_empty() {
+            return;
+        }
         let i = match self.role_table_state.selected() {
             Some(i) => {
                 if i == 0 {
@@ -408,9 +1341,12 @@ impl App {
     }
 
     pub fn next(&mut self) {
+        if self.visible_rows.is_empty() {
+            return;
+        }
         let i = match self.table_state.selected() {
             Some(i) => {
-                if i >= self.rows.len() - 1 {
+                if i >= self.visible_rows.len() - 1 {
                     0
                 } else {
                     i + 1
@@ -418,24 +1354,68 @@ impl App {
             }
             None => 0,
         };
-        self.selected_account = self.rows[i].clone();
+        self.selected_account = self.rows[self.visible_rows[i]].clone();
         self.table_state.select(Some(i));
         self.scroll_state = self.scroll_state.position(i * ITEM_HEIGHT);
     }
 
     pub fn previous(&mut self) {
+        if self.visible_rows.is_empty() {
+            return;
+        }
         let i = match self.table_state.selected() {
             Some(i) => {
                 if i == 0 {
-                    self.rows.len() - 1
+                    self.visible_rows.len() - 1
                 } else {
                     i - 1
                 }
             }
             None => 0,
         };
-        self.selected_account = self.rows[i].clone();
+        self.selected_account = self.rows[self.visible_rows[i]].clone();
         self.table_state.select(Some(i));
         self.scroll_state = self.scroll_state.position(i * ITEM_HEIGHT);
     }
-}
\ No newline at end of file
+}
+
+/// Per-role settings as the account editor shows them: `Role=value ...`.
+/// Entries are separated by spaces rather than commas, since console URLs
+/// can contain commas but never spaces.
+fn format_role_settings<T: std::fmt::Display>(settings: &BTreeMap<String, T>) -> String {
+    settings.iter()
+        .map(|(role, value)| format!("{}={}", role, value))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn parse_role_settings(text: &str, what: &str) -> anyhow::Result<BTreeMap<String, String>> {
+    let mut settings = BTreeMap::new();
+    for entry in text.split_whitespace() {
+        match entry.split_once('=') {
+            Some((role, value)) if !role.is_empty() && !value.is_empty() => {
+                settings.insert(role.to_string(), value.to_string());
+            }
+            _ => anyhow::bail!("{} are written as Role=value, not {}", what, entry),
+        }
+    }
+    Ok(settings)
+}
+
+fn parse_seconds(text: &str) -> anyhow::Result<u32> {
+    text.parse().map_err(|_| anyhow::anyhow!("session lengths are a number of seconds, not {}", text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_role_settings, parse_role_settings};
+
+    #[test]
+    fn keeps_commas_in_per_role_settings() {
+        let text = "Billing=costmanagement/home#/cost-explorer Ops=https://console.aws.amazon.com/ec2/home#Instances:instanceState=running,stopped";
+        let settings = parse_role_settings(text, "role consoles").unwrap();
+        assert_eq!(settings["Ops"], "https://console.aws.amazon.com/ec2/home#Instances:instanceState=running,stopped");
+        assert_eq!(format_role_settings(&settings), text);
+        assert!(parse_role_settings("Billing", "role consoles").is_err());
+    }
+}
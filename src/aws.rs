@@ -1,9 +1,21 @@
 pub mod account_info_provider;
 pub mod token;
 pub mod token_cache;
+pub mod inventory_cache;
 pub mod cli;
+pub mod retry;
+pub mod credential_cache;
+pub mod assume_role_provider;
+pub mod credentials_file;
+pub mod partition;
 
 pub use account_info_provider::*;
 pub use token::*;
 pub use token_cache::*;
-pub use cli::*;
\ No newline at end of file
+pub use inventory_cache::*;
+pub use cli::*;
+pub use retry::*;
+pub use credential_cache::*;
+pub use assume_role_provider::*;
+pub use credentials_file::*;
+pub use partition::*;
\ No newline at end of file
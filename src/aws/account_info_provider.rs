@@ -2,13 +2,15 @@ use aws_sdk_sso::{operation::get_role_credentials::GetRoleCredentialsOutput, Cli
 use anyhow::Result;
 use std::fmt::Display;
 use serde::{ Deserialize, Serialize };
-use super::AccessToken;
+use super::{map_sdk_error, AccessToken};
 
 #[derive(Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Debug, Clone)]
 pub struct AccountInfo {
     pub account_name: String,
     pub account_id: String,
     pub roles: Vec<String>,
+    #[serde(default)]
+    pub email: String,
 }
 
 impl Display for AccountInfo {
@@ -23,44 +25,71 @@ pub struct AccountInfoProvider {
 }
 
 impl AccountInfoProvider {
-    pub fn new(sdk_config: &aws_config::SdkConfig) -> Self {
+    pub fn new(sdk_config: &aws_config::SdkConfig, endpoint_url: Option<&str>) -> Self {
+        let mut config = aws_sdk_sso::config::Builder::from(sdk_config);
+        if let Some(endpoint_url) = endpoint_url {
+            config = config.endpoint_url(endpoint_url);
+        }
         AccountInfoProvider { 
-            client: Client::new(sdk_config)
+            client: Client::from_conf(config.build())
          }
     }
 
     pub async fn get_account_list(&self, access_token: &AccessToken) -> Result<Vec<AccountInfo>> {
-        let list_accounts = self.client.list_accounts()
-            .access_token(access_token.access_token.as_str())
-            .max_results(300)
-            .send().await?;
-    
-        let account_infos = list_accounts.account_list().iter()
-            .map(|account| {
-                AccountInfo {
-                    account_id: String::from(account.account_id().unwrap()),
-                    account_name: String::from(account.account_name().unwrap_or("unknown")),
-                    roles: vec![]
-                }
-            })
-            .collect::<Vec<_>>();
-    
+        let mut account_infos = vec![];
+        let mut next_token = None;
+        loop {
+            let list_accounts = self.client.list_accounts()
+                .access_token(access_token.access_token.as_str())
+                .max_results(100)
+                .set_next_token(next_token)
+                .send().await.map_err(map_sdk_error)?;
+
+            account_infos.extend(
+                list_accounts.account_list().iter()
+                    .map(|account| {
+                        AccountInfo {
+                            account_id: String::from(account.account_id().unwrap()),
+                            account_name: String::from(account.account_name().unwrap_or("unknown")),
+                            roles: vec![],
+                            email: String::from(account.email_address().unwrap_or_default()),
+                        }
+                    })
+            );
+
+            next_token = list_accounts.next_token().map(String::from);
+            if next_token.is_none() {
+                break;
+            }
+        }
+
         Ok(account_infos)
     }
 
     pub async fn get_roles_for_account(&self, access_token: &AccessToken, account_info: &AccountInfo) -> Result<Vec<String>>{
-        let account_roles = self.client.list_account_roles()
-            .access_token(access_token.access_token.as_str())
-            .account_id(account_info.account_id.as_str())
-            .max_results(10)
-            .send().await?;
-    
-        Ok(
-            account_roles.role_list().iter()
-                .map(|r| r.role_name().unwrap() )
-                .map(String::from)
-                .collect::<Vec<_>>()
-        )
+        let mut roles = vec![];
+        let mut next_token = None;
+        loop {
+            let account_roles = self.client.list_account_roles()
+                .access_token(access_token.access_token.as_str())
+                .account_id(account_info.account_id.as_str())
+                .max_results(100)
+                .set_next_token(next_token)
+                .send().await.map_err(map_sdk_error)?;
+
+            roles.extend(
+                account_roles.role_list().iter()
+                    .filter_map(|r| r.role_name())
+                    .map(String::from)
+            );
+
+            next_token = account_roles.next_token().map(String::from);
+            if next_token.is_none() {
+                break;
+            }
+        }
+
+        Ok(roles)
     }
 
     pub async fn get_role_credentials(&self, access_token: &AccessToken, account_info: &AccountInfo, role: &str) -> Result<GetRoleCredentialsOutput> {
@@ -68,7 +97,7 @@ impl AccountInfoProvider {
             .access_token(access_token.access_token.as_str())
             .account_id(account_info.account_id.as_str())
             .role_name(role)
-            .send().await?;
+            .send().await.map_err(map_sdk_error)?;
     
         //println!("Role credentials: {:?}", role_credentials);
     
@@ -0,0 +1,71 @@
+use anyhow::{anyhow, Result};
+use aws_sdk_sts::{config::Credentials, types::Tag, Client};
+use chrono::DateTime;
+use crate::{metadata::ChainedRole, sso::RoleCredentials};
+use super::map_sdk_error;
+
+/// Assumes IAM roles through STS, signing with credentials we already hold
+/// (usually the ones `GetRoleCredentials` returned for an SSO role).
+#[derive(Clone)]
+pub struct AssumeRoleProvider {
+    sdk_config: aws_config::SdkConfig,
+    endpoint_url: Option<String>,
+}
+
+impl AssumeRoleProvider {
+    pub fn new(sdk_config: &aws_config::SdkConfig, endpoint_url: Option<&str>) -> Self {
+        AssumeRoleProvider {
+            sdk_config: sdk_config.clone(),
+            endpoint_url: endpoint_url.map(String::from),
+        }
+    }
+
+    /// Assumes `chained` as `session_name`. `token_code` is the current code
+    /// of the role's MFA device, if it has one.
+    pub async fn assume_role(&self, source: &RoleCredentials, chained: &ChainedRole, session_name: &str, token_code: Option<&str>) -> Result<RoleCredentials> {
+        let credentials = Credentials::new(
+            &source.access_key_id,
+            &source.secret_access_key,
+            Some(source.session_token.clone()),
+            None,
+            "assumer",
+        );
+        let mut config = aws_sdk_sts::config::Builder::from(&self.sdk_config)
+            .credentials_provider(credentials);
+        if let Some(endpoint_url) = &self.endpoint_url {
+            config = config.endpoint_url(endpoint_url);
+        }
+        let client = Client::from_conf(config.build());
+
+        let tags = chained.tags.iter()
+            .map(|(key, value)| Tag::builder().key(key).value(value).build())
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut request = client.assume_role()
+            .role_arn(&chained.role_arn)
+            .role_session_name(session_name)
+            .set_duration_seconds(chained.duration_seconds)
+            .set_external_id(chained.external_id.clone())
+            .set_tags((!tags.is_empty()).then_some(tags));
+        if let Some(mfa_serial) = &chained.mfa_serial {
+            let token_code = token_code
+                .ok_or_else(|| anyhow!("{} needs a token code from {}", chained.role_arn, mfa_serial))?;
+            request = request.serial_number(mfa_serial).token_code(token_code);
+        }
+
+        let output = request.send()
+            .await
+            .map_err(map_sdk_error)?;
+        let credentials = output.credentials()
+            .ok_or_else(|| anyhow!("AssumeRole returned no credentials for {}", chained.role_arn))?;
+        let expiration = credentials.expiration();
+
+        Ok(RoleCredentials {
+            name: chained.label(),
+            access_key_id: credentials.access_key_id().to_string(),
+            secret_access_key: credentials.secret_access_key().to_string(),
+            session_token: credentials.session_token().to_string(),
+            expiration: DateTime::from_timestamp(expiration.secs(), expiration.subsec_nanos())
+                .ok_or_else(|| anyhow!("AssumeRole returned an invalid expiration"))?,
+        })
+    }
+}
@@ -0,0 +1,75 @@
+use anyhow::Result;
+use chrono::Duration;
+use std::{collections::HashMap, fs, path::{Path, PathBuf}};
+use crate::{sso::RoleCredentials, utils::json};
+
+/// Role credentials keyed by account and role, kept in memory and, when a
+/// cache directory is given, on disk so that separate runs (the CLI,
+/// `credential_process`) can reuse them too. Entries are only handed out
+/// while they stay valid for longer than `margin`.
+#[derive(Clone, Default)]
+pub struct RoleCredentialsCache {
+    cache_dir: Option<PathBuf>,
+    start_url: String,
+    margin: Duration,
+    entries: HashMap<(String, String), RoleCredentials>,
+}
+
+impl RoleCredentialsCache {
+    pub fn new(start_url: &str, cache_dir: Option<&Path>, margin: Duration) -> Self {
+        Self {
+            cache_dir: cache_dir.map(Path::to_path_buf),
+            start_url: String::from(start_url),
+            margin,
+            entries: HashMap::new(),
+        }
+    }
+
+    pub fn get(&mut self, account_id: &str, role: &str) -> Option<RoleCredentials> {
+        let key = (account_id.to_string(), role.to_string());
+        if let Some(credentials) = self.entries.get(&key) {
+            if self.is_usable(credentials) {
+                return Some(credentials.clone());
+            }
+            self.entries.remove(&key);
+        }
+
+        let cache_file_path = self.cache_file_path(account_id, role)?;
+        let credentials: RoleCredentials = json::read_from_file(&cache_file_path).ok()?;
+        if !self.is_usable(&credentials) {
+            let _ = fs::remove_file(cache_file_path);
+            return None;
+        }
+        self.entries.insert(key, credentials.clone());
+        Some(credentials)
+    }
+
+    pub fn cache_credentials(&mut self, account_id: &str, role: &str, credentials: &RoleCredentials) -> Result<()> {
+        self.entries.insert((account_id.to_string(), role.to_string()), credentials.clone());
+        if let (Some(cache_dir), Some(cache_file_path)) = (&self.cache_dir, self.cache_file_path(account_id, role)) {
+            if !cache_dir.exists() {
+                fs::create_dir_all(cache_dir)?;
+            }
+            json::write_to_private_file(&cache_file_path, credentials)?;
+        }
+        Ok(())
+    }
+
+    fn is_usable(&self, credentials: &RoleCredentials) -> bool {
+        !credentials.expires_within(self.margin)
+    }
+
+    fn cache_file_path(&self, account_id: &str, role: &str) -> Option<PathBuf> {
+        let cache_dir = self.cache_dir.as_ref()?;
+        Some(cache_dir.join(format!("credentials-{}.json", self.hash_key(account_id, role))))
+    }
+
+    fn hash_key(&self, account_id: &str, role: &str) -> String {
+        use sha1::{Sha1, Digest};
+
+        let mut hasher = Sha1::new();
+        hasher.update(format!("{}|{}|{}", self.start_url, account_id, role));
+
+        format!("{:02x}", hasher.finalize())
+    }
+}
@@ -0,0 +1,73 @@
+use anyhow::{bail, Result};
+use chrono::SecondsFormat;
+use std::path::{Path, PathBuf};
+use crate::{sso::RoleCredentials, utils::ini_file::IniFile};
+
+/// Keys written by older versions of assumer, which used environment
+/// variable names. They are dropped from any profile we rewrite.
+const LEGACY_KEYS: &[&str] = &["AWS_ACCESS_KEY_ID", "AWS_SECRET_ACCESS_KEY", "AWS_SESSION_TOKEN"];
+
+/// Marks profiles written by assumer, in both the config and credentials
+/// files, so they can be found again and pruned.
+pub const MANAGED_KEY: &str = "x_assumer_managed";
+
+/// Writes role credentials into a single profile of an AWS shared
+/// credentials file, leaving every other line of the file as it was.
+pub struct CredentialsFile {
+    file_path: PathBuf,
+}
+
+impl CredentialsFile {
+    pub fn new(file_path: &Path) -> Self {
+        Self {
+            file_path: file_path.to_path_buf(),
+        }
+    }
+
+    /// Where the previous contents are kept before each write.
+    pub fn backup_path(&self) -> PathBuf {
+        let mut file_name = self.file_path.file_name().unwrap_or_default().to_os_string();
+        file_name.push(".assumer-backup");
+        self.file_path.with_file_name(file_name)
+    }
+
+    /// Whether `profile` exists with settings assumer didn't write, such as
+    /// long-term keys, which writing credentials to it would replace.
+    pub fn is_unmanaged(&self, profile: &str) -> Result<bool> {
+        Ok(is_unmanaged(&IniFile::load(&self.file_path)?, profile))
+    }
+
+    /// Writes `credentials` to `profile`. A profile assumer didn't write is
+    /// only replaced with `overwrite`, since pruning later removes it whole.
+    /// The backup is only readable by the current user.
+    pub fn write_profile(&self, profile: &str, account_id: &str, credentials: &RoleCredentials, overwrite: bool) -> Result<()> {
+        let mut file = IniFile::load(&self.file_path)?;
+        if !overwrite && is_unmanaged(&file, profile) {
+            bail!("[{}] in {} wasn't written by assumer", profile, self.file_path.display());
+        }
+        if self.file_path.exists() {
+            file.write_to_private_file(&self.backup_path())?;
+        }
+
+        let expires = credentials.expiration.to_rfc3339_opts(SecondsFormat::Secs, true);
+        for key in LEGACY_KEYS {
+            file.remove_key(profile, key);
+        }
+        file.set(profile, "aws_access_key_id", &credentials.access_key_id);
+        file.set(profile, "aws_secret_access_key", &credentials.secret_access_key);
+        file.set(profile, "aws_session_token", &credentials.session_token);
+        file.set(profile, "x_security_token_expires", &expires);
+        file.set(profile, MANAGED_KEY, "true");
+        file.set(profile, "x_assumer_account_id", account_id);
+        file.set(profile, "x_assumer_role", &credentials.name);
+        file.write_to_private_file(&self.file_path)
+    }
+}
+
+/// Profiles written by older versions are recognised by their
+/// environment variable style keys.
+fn is_unmanaged(file: &IniFile, profile: &str) -> bool {
+    file.has_section(profile)
+        && file.get(profile, MANAGED_KEY) != Some("true")
+        && !LEGACY_KEYS.iter().any(|key| file.get(profile, key).is_some())
+}
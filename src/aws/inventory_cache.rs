@@ -0,0 +1,67 @@
+use super::AccountInfo;
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::{fs, path::{Path, PathBuf}};
+use crate::utils::{json, serde::json_date_format};
+
+/// Snapshot of the accounts and roles visible through one SSO start URL.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Inventory {
+    pub start_url: String,
+    #[serde(with = "json_date_format")]
+    pub synced_at: DateTime<Utc>,
+    pub accounts: Vec<AccountInfo>,
+}
+
+impl Inventory {
+    pub fn is_fresh(&self, ttl: Duration) -> bool {
+        self.synced_at + ttl > Utc::now()
+    }
+}
+
+#[derive(Clone)]
+pub struct InventoryCache {
+    cache_dir: PathBuf,
+    start_url: String,
+}
+
+impl InventoryCache {
+    pub fn new(start_url: &str, cache_dir: &Path) -> Self {
+        Self {
+            cache_dir: cache_dir.to_path_buf(),
+            start_url: String::from(start_url),
+        }
+    }
+
+    pub fn get_cached_inventory(&self) -> Result<Inventory> {
+        json::read_from_file(self.cache_file_path().as_path())
+    }
+
+    pub fn cache_inventory(&self, accounts: Vec<AccountInfo>) -> Result<Inventory> {
+        if !self.cache_dir.exists() {
+            fs::create_dir_all(&self.cache_dir)?;
+        }
+        let inventory = Inventory {
+            start_url: self.start_url.clone(),
+            synced_at: Utc::now(),
+            accounts,
+        };
+        json::write_to_file(self.cache_file_path().as_path(), &inventory)?;
+        Ok(inventory)
+    }
+
+    fn cache_file_path(&self) -> PathBuf {
+        self.cache_dir.join(format!("inventory-{}.json", self.hash_key()))
+    }
+
+    fn hash_key(&self) -> String {
+        use sha1::{Sha1, Digest};
+
+        let mut hasher = Sha1::new();
+        hasher.update(self.start_url.as_str());
+
+        format!("{:02x}", hasher.finalize())
+    }
+}
@@ -0,0 +1,68 @@
+/// An AWS partition: a group of regions with their own accounts, sign-in
+/// and console.
+#[derive(Debug, PartialEq)]
+pub struct Partition {
+    /// The partition as it appears in ARNs.
+    pub id: &'static str,
+    /// Domain of the regional service endpoints.
+    pub dns_suffix: &'static str,
+    pub signin_url: &'static str,
+    pub console_url: &'static str,
+    /// Domain of IAM Identity Center start URLs.
+    pub start_url_suffix: &'static str,
+}
+
+pub const PARTITIONS: &[Partition] = &[
+    Partition {
+        id: "aws",
+        dns_suffix: "amazonaws.com",
+        signin_url: "https://signin.aws.amazon.com/",
+        console_url: "https://console.aws.amazon.com/",
+        start_url_suffix: ".awsapps.com",
+    },
+    Partition {
+        id: "aws-us-gov",
+        dns_suffix: "amazonaws.com",
+        signin_url: "https://signin.amazonaws-us-gov.com/",
+        console_url: "https://console.amazonaws-us-gov.com/",
+        start_url_suffix: ".awsapps.com",
+    },
+    Partition {
+        id: "aws-cn",
+        dns_suffix: "amazonaws.com.cn",
+        signin_url: "https://signin.amazonaws.cn/",
+        console_url: "https://console.amazonaws.cn/",
+        start_url_suffix: ".awsapps.cn",
+    },
+];
+
+impl Partition {
+    /// The partition `region` belongs to. Unknown regions are taken to be
+    /// commercial ones.
+    pub fn from_region(region: &str) -> &'static Partition {
+        let id = if region.starts_with("us-gov-") {
+            "aws-us-gov"
+        } else if region.starts_with("cn-") {
+            "aws-cn"
+        } else {
+            "aws"
+        };
+        Self::from_id(id).unwrap()
+    }
+
+    pub fn from_id(id: &str) -> Option<&'static Partition> {
+        PARTITIONS.iter().find(|partition| partition.id == id)
+    }
+
+    /// The endpoint that trades credentials for a console sign-in token.
+    pub fn federation_endpoint(&self) -> String {
+        format!("{}federation", self.signin_url)
+    }
+
+    /// Whether `start_url` is an IAM Identity Center portal in this
+    /// partition.
+    pub fn has_start_url(&self, start_url: &str) -> bool {
+        let host = start_url.trim().trim_start_matches("https://").split('/').next().unwrap_or_default();
+        host.ends_with(self.start_url_suffix)
+    }
+}
@@ -0,0 +1,52 @@
+use std::{error::Error, fmt, time::Duration};
+use aws_config::retry::RetryConfig;
+use aws_sdk_sso::error::{ProvideErrorMetadata, SdkError};
+
+/// AWS error codes meaning the request was throttled rather than refused.
+const THROTTLING_ERRORS: &[&str] = &[
+    "TooManyRequestsException",
+    "ThrottlingException",
+    "Throttling",
+    "SlowDownException",
+    "RequestLimitExceeded",
+];
+
+/// Retry policy shared by the SSO and OIDC clients: exponential backoff with
+/// full jitter, capped at `max_backoff` between attempts.
+pub fn retry_config(max_attempts: u32, max_backoff: Duration) -> RetryConfig {
+    RetryConfig::standard()
+        .with_max_attempts(max_attempts.max(1))
+        .with_max_backoff(max_backoff)
+}
+
+/// Returned once the SDK has given up retrying a throttled call, so the UI
+/// can tell "slow down" apart from a real failure.
+#[derive(Debug)]
+pub struct ThrottledError {
+    code: String,
+}
+
+impl fmt::Display for ThrottledError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Throttled by AWS ({}), try again shortly", self.code)
+    }
+}
+
+impl Error for ThrottledError {}
+
+/// Converts an SDK error into an [anyhow::Error], turning throttling
+/// responses into a [ThrottledError].
+pub fn map_sdk_error<E, R>(err: SdkError<E, R>) -> anyhow::Error
+where
+    E: ProvideErrorMetadata + Error + Send + Sync + 'static,
+    R: fmt::Debug + Send + Sync + 'static,
+{
+    match err.code() {
+        Some(code) if THROTTLING_ERRORS.contains(&code) => ThrottledError { code: code.to_string() }.into(),
+        _ => err.into(),
+    }
+}
+
+pub fn is_throttled(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<ThrottledError>().is_some()
+}
@@ -6,6 +6,9 @@ use aws_sdk_ssooidc::Client;
 use chrono::{DateTime, Duration, TimeZone, Utc};
 use serde::{Deserialize, Serialize};
 
+/// The scopes assumer registers its OIDC client with, which `sso-session`
+/// profiles need to ask for too to share its token.
+pub const REGISTRATION_SCOPES: &str = "sso:account:access";
 
 #[derive(Serialize, Deserialize, PartialEq, Debug, Default, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -51,14 +54,18 @@ impl SsoAccessTokenProvider {
     const DEVICE_GRANT_TYPE: &'static str = "urn:ietf:params:oauth:grant-type:device_code";
     const REFRESH_GRANT_TYPE: &'static str = "refresh_token";
 
-    pub fn new(config: &SdkConfig, sso_session_name: &str, config_dir: &Path) -> anyhow::Result<Self> {
+    pub fn new(config: &SdkConfig, sso_session_name: &str, config_dir: &Path, endpoint_url: Option<&str>) -> anyhow::Result<Self> {
         let sso_cache_dir = config_dir.join("sso").join("cache");
         if !sso_cache_dir.exists() {
             fs::create_dir_all(&sso_cache_dir)?;
         }
+        let mut client_config = aws_sdk_ssooidc::config::Builder::from(config);
+        if let Some(endpoint_url) = endpoint_url {
+            client_config = client_config.endpoint_url(endpoint_url);
+        }
         Ok(Self {
             sso_session_name: String::from(sso_session_name),
-            client: Client::new(config),
+            client: Client::from_conf(client_config.build()),
             cache: super::AccessTokenCache::new(
                 sso_session_name,
                 sso_cache_dir.as_path(),
@@ -81,6 +88,19 @@ impl SsoAccessTokenProvider {
         }
     }
 
+    /// Returns the cached token, refreshing it if it has expired. Never
+    /// starts the interactive device authorization, so it is safe to call
+    /// from background jobs.
+    pub async fn get_cached_access_token(&self) -> Result<AccessToken> {
+        let cached_token = self.cache.get_cached_token()?;
+        if cached_token.is_expired() {
+            self.refresh_token(cached_token).await
+                .map_err(|_| anyhow!("SSO session expired, log in again"))
+        } else {
+            Ok(cached_token)
+        }
+    }
+
     async fn get_new_token(&self, start_url: &str, app: &mut App) -> Result<AccessToken> {
         let device_client = self.register_device_client().await?;
         self.authenticate(start_url, device_client, app).await
@@ -92,9 +112,10 @@ impl SsoAccessTokenProvider {
             .register_client()
             .client_name(format!("{}-{}", Self::CLIENT_NAME, self.sso_session_name))
             .client_type("public")
-            .scopes("sso:account:access")
+            .scopes(REGISTRATION_SCOPES)
             .send()
-            .await?;
+            .await
+            .map_err(super::map_sdk_error)?;
         let client_id = response.client_id().unwrap();
         let client_secret = response.client_secret().unwrap();
         let registration_expires_at = Utc
@@ -116,13 +137,14 @@ impl SsoAccessTokenProvider {
             .client_secret(device_client.client_secret.as_str())
             .start_url(start_url)
             .send()
-            .await?;
+            .await
+            .map_err(super::map_sdk_error)?;
 
         open::that(auth_response.verification_uri_complete().unwrap())?;
 
         app.token_prompt = format!("Verify authorization code: {}", auth_response.user_code().unwrap());
 
-        let interval = auth_response.interval();
+        let mut interval = auth_response.interval();
         loop {
             let token_response = self
                 .client
@@ -141,7 +163,7 @@ impl SsoAccessTokenProvider {
                     let expires_at = Utc::now() + Duration::seconds(out.expires_in() as i64);
 
                     let access_token = AccessToken {
-                        region: String::from(self.client.config().region().unwrap().to_string()),
+                        region: self.client.config().region().unwrap().to_string(),
                         start_url: String::from(start_url),
                         access_token: String::from(access_token),
                         expires_at,
@@ -158,6 +180,10 @@ impl SsoAccessTokenProvider {
                     if service_error.is_access_denied_exception() {
                         break Err(anyhow!("Access request rejected"));
                     } 
+                    if service_error.is_slow_down_exception() {
+                        // RFC 8628: back off by five seconds on every slow_down
+                        interval += 5;
+                    }
 
                     let millis = Duration::seconds(interval as i64);
                     std::thread::sleep(millis.to_std()?);
@@ -176,14 +202,15 @@ impl SsoAccessTokenProvider {
             .grant_type(Self::REFRESH_GRANT_TYPE)
             .refresh_token(cached_token.refresh_token.as_str())
             .send()
-            .await?;
+            .await
+            .map_err(super::map_sdk_error)?;
 
         let access_token = response.access_token().unwrap();
         let refresh_token = response.refresh_token().unwrap();
         let expires_at = Utc::now() + Duration::seconds(response.expires_in() as i64);
 
         let new_access_token = AccessToken {
-            region: String::from(self.client.config().region().unwrap().to_string()),
+            region: self.client.config().region().unwrap().to_string(),
             start_url: cached_token.start_url.clone(),
             access_token: String::from(access_token),
             expires_at,
@@ -191,6 +218,6 @@ impl SsoAccessTokenProvider {
             refresh_token: String::from(refresh_token),
         };
 
-        Ok(self.cache.cache_token(new_access_token)?)
+        self.cache.cache_token(new_access_token)
     }
 }
\ No newline at end of file
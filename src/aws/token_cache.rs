@@ -24,7 +24,7 @@ impl AccessTokenCache {
     
     pub fn cache_token(&self, access_token: AccessToken) -> Result<AccessToken> {
         let cache_file_path = self.cache_dir.join(format!("{}.json", self.hash_key()));
-        json::write_to_file(cache_file_path.as_path(), &access_token)?;
+        json::write_to_private_file(cache_file_path.as_path(), &access_token)?;
         Ok(access_token)
     } 
     
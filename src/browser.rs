@@ -0,0 +1,207 @@
+use anyhow::{anyhow, bail, Result};
+use std::process::{Command, Stdio};
+use urlencoding::encode;
+use crate::{sso, ConfigOptions};
+
+/// Who the console is being opened for, so launchers can keep each
+/// account's or role's session apart.
+pub struct Target<'a> {
+    pub account_id: &'a str,
+    pub role: &'a str,
+}
+
+impl Target<'_> {
+    /// Name of the browser container for the role.
+    pub fn container(&self) -> String {
+        format!("aws-sso-{}-{}", self.account_id, self.role)
+    }
+}
+
+/// Opens a federated sign-in URL in some browser.
+pub trait Launcher {
+    /// Short identifier used in the `browser` config option.
+    fn name(&self) -> &'static str;
+    fn description(&self) -> &'static str;
+    fn launch(&self, url: &str, target: &Target) -> Result<()>;
+}
+
+/// The Granted Firefox extension's `ext+granted-containers:` links.
+pub struct GrantedContainers;
+
+impl Launcher for GrantedContainers {
+    fn name(&self) -> &'static str {
+        "granted"
+    }
+
+    fn description(&self) -> &'static str {
+        "Firefox container per role, through the Granted extension"
+    }
+
+    fn launch(&self, url: &str, target: &Target) -> Result<()> {
+        firefox(&format!("ext+granted-containers:name={}&url={}", target.container(), encode(url)))
+    }
+}
+
+/// `ext+container:` links, handled by the Open external links in a
+/// container extension on top of Firefox Multi-Account Containers.
+pub struct FirefoxContainers;
+
+impl Launcher for FirefoxContainers {
+    fn name(&self) -> &'static str {
+        "firefox-container"
+    }
+
+    fn description(&self) -> &'static str {
+        "Firefox Multi-Account Containers container per role"
+    }
+
+    fn launch(&self, url: &str, target: &Target) -> Result<()> {
+        firefox(&format!("ext+container:name={}&url={}", target.container(), encode(url)))
+    }
+}
+
+/// Chrome or Chromium with a separate profile directory per account, so
+/// sessions in different accounts don't sign each other out.
+pub struct Chrome;
+
+impl Launcher for Chrome {
+    fn name(&self) -> &'static str {
+        "chrome"
+    }
+
+    fn description(&self) -> &'static str {
+        "Chrome or Chromium with a profile per account"
+    }
+
+    fn launch(&self, url: &str, target: &Target) -> Result<()> {
+        let user_data_dir = sso::get_assumer_path().join("chrome").join(target.account_id);
+        let args = [
+            format!("--user-data-dir={}", user_data_dir.display()),
+            "--no-first-run".to_string(),
+            "--new-window".to_string(),
+            url.to_string(),
+        ];
+        if cfg!(target_os = "macos") {
+            return run("open", &["-na".to_string(), "Google Chrome".to_string(), "--args".to_string()].into_iter().chain(args).collect::<Vec<_>>());
+        }
+        let programs: &[&str] = if cfg!(target_os = "windows") {
+            &["chrome"]
+        } else {
+            &["google-chrome", "google-chrome-stable", "chromium", "chromium-browser"]
+        };
+        for program in programs {
+            if spawn(program, &args).is_ok() {
+                return Ok(());
+            }
+        }
+        Err(anyhow!("Could not find Chrome or Chromium (tried {})", programs.join(", ")))
+    }
+}
+
+/// Whatever the system opens links with.
+pub struct SystemDefault;
+
+impl Launcher for SystemDefault {
+    fn name(&self) -> &'static str {
+        "default"
+    }
+
+    fn description(&self) -> &'static str {
+        "The system's default browser"
+    }
+
+    fn launch(&self, url: &str, _target: &Target) -> Result<()> {
+        webbrowser::open(url)?;
+        Ok(())
+    }
+}
+
+/// A command from the `browser_command` config option. Each word may use
+/// `{url}`, `{container}`, `{account_id}` and `{role}`.
+pub struct Custom {
+    pub template: String,
+}
+
+impl Launcher for Custom {
+    fn name(&self) -> &'static str {
+        "custom"
+    }
+
+    fn description(&self) -> &'static str {
+        "The command in browser_command"
+    }
+
+    fn launch(&self, url: &str, target: &Target) -> Result<()> {
+        let container = target.container();
+        let words: Vec<String> = self.template.split_whitespace()
+            .map(|word| word
+                .replace("{url}", url)
+                .replace("{container}", &container)
+                .replace("{account_id}", target.account_id)
+                .replace("{role}", target.role))
+            .collect();
+        let Some((program, args)) = words.split_first() else {
+            bail!("browser is custom but browser_command is empty");
+        };
+        spawn(program, args)
+    }
+}
+
+/// Every launcher, in the order they're documented.
+pub fn all(config_options: &ConfigOptions) -> Vec<Box<dyn Launcher>> {
+    vec![
+        Box::new(GrantedContainers),
+        Box::new(FirefoxContainers),
+        Box::new(Chrome),
+        Box::new(SystemDefault),
+        Box::new(Custom { template: config_options.value("browser_command") }),
+    ]
+}
+
+/// The launcher named by the `browser` config option.
+pub fn from_config(config_options: &ConfigOptions) -> Result<Box<dyn Launcher>> {
+    let name = config_options.value("browser");
+    let name = name.trim();
+    all(config_options).into_iter()
+        .find(|launcher| launcher.name() == name)
+        .ok_or_else(|| anyhow!("Unknown browser {}, expected one of granted, firefox-container, chrome, default or custom", name))
+}
+
+/// Opens `link` in a new Firefox tab.
+fn firefox(link: &str) -> Result<()> {
+    if cfg!(target_os = "windows") {
+        run("powershell", &["-Command", "Start-Process", "firefox", "-ArgumentList", &format!("'--new-tab', '{}'", link)])
+    } else if cfg!(target_os = "macos") {
+        run("open", &["-na", "Firefox", "--args", "--new-tab", link])
+    } else {
+        spawn("firefox", &["--new-tab", link])
+    }
+}
+
+/// Runs a command that hands off to the browser and exits.
+fn run<S: AsRef<std::ffi::OsStr>>(program: &str, args: &[S]) -> Result<()> {
+    let status = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map_err(|err| anyhow!("Could not run {}: {}", program, err))?;
+    if !status.success() {
+        bail!("{} exited with {}", program, status);
+    }
+    Ok(())
+}
+
+/// Starts a browser without waiting for it, since a new instance only
+/// exits once its window is closed.
+fn spawn<S: AsRef<std::ffi::OsStr>>(program: &str, args: &[S]) -> Result<()> {
+    Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|err| anyhow!("Could not run {}: {}", program, err))?;
+    Ok(())
+}
@@ -0,0 +1,159 @@
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+    time::Duration,
+};
+
+/// How text ends up on the clipboard.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Backend {
+    /// A platform tool such as `pbcopy`, `wl-copy` or `xclip`.
+    Native,
+    /// An OSC 52 escape sequence, which the terminal turns into a clipboard
+    /// write. Works over SSH and inside tmux, but can't be read back.
+    Osc52,
+}
+
+impl Backend {
+    /// Picks a backend from the `clipboard` config option: `native`, `osc52`
+    /// or `auto`, which prefers OSC 52 in remote sessions.
+    pub fn from_config(value: &str) -> Backend {
+        match value.trim() {
+            "native" => Backend::Native,
+            "osc52" => Backend::Osc52,
+            _ if is_remote_session() => Backend::Osc52,
+            _ => Backend::Native,
+        }
+    }
+}
+
+fn is_remote_session() -> bool {
+    ["SSH_TTY", "SSH_CONNECTION", "SSH_CLIENT"].iter().any(|name| std::env::var_os(name).is_some())
+}
+
+/// Copies `text`, falling back to OSC 52 when no native tool works. Returns
+/// the backend that was actually used.
+pub fn copy(text: &str, backend: Backend) -> Result<Backend> {
+    if backend == Backend::Native && native_copy(text).is_ok() {
+        return Ok(Backend::Native);
+    }
+    osc52_copy(text)?;
+    Ok(Backend::Osc52)
+}
+
+/// Fingerprint of copied text, so we can tell later whether the clipboard
+/// still holds it without keeping the secret itself around.
+pub fn digest(text: &str) -> String {
+    use sha1::{Sha1, Digest};
+
+    let mut hasher = Sha1::new();
+    hasher.update(text.trim_end());
+    format!("{:02x}", hasher.finalize())
+}
+
+/// Empties the clipboard, unless it is known to hold something other than
+/// the text behind `copied_digest` by now, i.e. the user has copied
+/// something else since.
+pub fn clear(copied_digest: &str, backend: Backend) -> Result<()> {
+    if backend == Backend::Native {
+        if let Ok(current) = native_paste() {
+            if digest(&current) != copied_digest {
+                return Ok(());
+            }
+        }
+    }
+    copy("", backend).map(|_| ())
+}
+
+/// Clears the clipboard after `delay` from a detached `assumer` process, so
+/// it still happens once we have exited. Only possible for the native
+/// backend, as OSC 52 needs the terminal.
+pub fn clear_later(copied_digest: &str, backend: Backend, delay: Duration) -> Result<()> {
+    if backend != Backend::Native {
+        return Err(anyhow!("Can't clear an OSC 52 clipboard after exiting"));
+    }
+    Command::new(std::env::current_exe()?)
+        .args([CLEAR_COMMAND, &delay.as_secs().to_string(), copied_digest])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    Ok(())
+}
+
+/// The hidden command [clear_later] runs.
+pub const CLEAR_COMMAND: &str = "__clear-clipboard";
+
+fn native_copy(text: &str) -> Result<()> {
+    for (program, args) in copy_commands() {
+        let child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        let Ok(mut child) = child else { continue };
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(text.as_bytes())?;
+        }
+        if child.wait()?.success() {
+            return Ok(());
+        }
+    }
+    Err(anyhow!("No clipboard tool found"))
+}
+
+fn native_paste() -> Result<String> {
+    for (program, args) in paste_commands() {
+        let Ok(output) = Command::new(program).args(args).stderr(Stdio::null()).output() else { continue };
+        if output.status.success() {
+            return Ok(String::from_utf8_lossy(&output.stdout).to_string());
+        }
+    }
+    Err(anyhow!("No clipboard tool found"))
+}
+
+fn copy_commands() -> Vec<(&'static str, Vec<&'static str>)> {
+    if cfg!(target_os = "macos") {
+        vec![("pbcopy", vec![])]
+    } else if cfg!(target_os = "windows") {
+        vec![("clip", vec![])]
+    } else {
+        vec![
+            ("wl-copy", vec![]),
+            ("xclip", vec!["-selection", "clipboard"]),
+            ("xsel", vec!["--clipboard", "--input"]),
+        ]
+    }
+}
+
+fn paste_commands() -> Vec<(&'static str, Vec<&'static str>)> {
+    if cfg!(target_os = "macos") {
+        vec![("pbpaste", vec![])]
+    } else if cfg!(target_os = "windows") {
+        vec![("powershell", vec!["-NoProfile", "-Command", "Get-Clipboard"])]
+    } else {
+        vec![
+            ("wl-paste", vec!["--no-newline"]),
+            ("xclip", vec!["-selection", "clipboard", "-o"]),
+            ("xsel", vec!["--clipboard", "--output"]),
+        ]
+    }
+}
+
+/// Writes the OSC 52 sequence for `text` to the terminal, wrapped in a DCS
+/// passthrough when running inside tmux.
+fn osc52_copy(text: &str) -> Result<()> {
+    let sequence = format!("\x1b]52;c;{}\x07", STANDARD.encode(text));
+    let sequence = if std::env::var_os("TMUX").is_some() {
+        format!("\x1bPtmux;{}\x1b\\", sequence.replace('\x1b', "\x1b\x1b"))
+    } else {
+        sequence
+    };
+    let mut stdout = std::io::stdout();
+    stdout.write_all(sequence.as_bytes())?;
+    stdout.flush()?;
+    Ok(())
+}
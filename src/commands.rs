@@ -0,0 +1,498 @@
+use color_eyre::{eyre::eyre, Report, Result};
+use std::io::{BufRead, Write};
+use std::time::Duration;
+use std::path::PathBuf;
+use crate::{browser, console, jobs::{self, JobEvent}, profiles, clipboard, exporters::{self, ExportContext, Exporter}, aws::{AccountInfo, CredentialsFile}, sso::{self, RoleCredentials}, App};
+
+const USAGE: &str = "\
+Usage: assumer [COMMAND]
+
+Without a command, starts the interactive UI.
+
+Commands:
+  accounts                      List the accounts you can access
+  roles <ACCOUNT>               List your roles in an account
+  credentials <ACCOUNT> <ROLE>  Print shell exports for a role's credentials
+  credential-process <ACCOUNT> <ROLE>
+                                Print a role's credentials for the AWS CLI's
+                                credential_process setting
+  export <ACCOUNT> <ROLE> [PROFILE] [--force]
+                                Write a role's credentials to a profile in
+                                ~/.aws/credentials (default: credentials_profile).
+                                Profiles assumer didn't write are only
+                                replaced with --force
+  export-as <FORMAT> <ACCOUNT> <ROLE> [TO]
+                                Print a role's credentials in FORMAT, or send
+                                them TO `clipboard`, `file` (export_directory)
+                                or a file path
+  copy <ACCOUNT> <ROLE> [FORMAT]
+                                Copy a role's credentials to the clipboard
+                                (default format: export_format)
+  console <ACCOUNT> <ROLE> [DESTINATION] [--print|--copy|--link]
+                                Open the AWS console signed in as a role, at
+                                DESTINATION (a URL or a path such as
+                                cloudwatch/home) or the configured destination
+  console --arn <ARN> [--role <ROLE>] [--account <ACCOUNT>] [--print|--copy|--link]
+                                Open the console at a resource, signed in to
+                                its account with ROLE or the first of
+                                console_roles. ARNs without an account (S3)
+                                need --account
+                                --print, --copy and --link print the sign-in
+                                URL, copy it or print it as a terminal
+                                hyperlink instead of opening a browser
+  formats                       List the export formats
+  browsers                      List the browser launchers for the console
+  sync-profiles [--dry-run|--yes]
+                                Write a profile to ~/.aws/config for every role
+                                in every account
+  migrate-profiles [--dry-run|--yes]
+                                Rename assumer's profiles in ~/.aws/config to
+                                match profile_name_template
+  prune [--dry-run|--yes]       Remove assumer-written profiles whose account
+                                or role is gone or whose credentials expired
+  help                          Show this message
+
+ACCOUNT is an account ID, name, alias or email address. ROLE is an SSO role
+or the name of a chained role set up for the account.";
+
+/// Runs a single non-interactive command, reading the same config as the UI.
+pub fn run(args: &[String]) -> Result<()> {
+    match args.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+        ["accounts"] => {
+            let mut app = connect()?;
+            for account in sso::get_sso_accounts(&mut app).map_err(report)? {
+                println!("{}\t{}\t{}", account.account_id, account.account_name, account.email);
+            }
+        }
+        ["roles", account] => {
+            let mut app = connect()?;
+            let account = find_account(&mut app, account)?;
+            let account_id = account.account_id.clone();
+            for role in sso::get_account_roles(&mut app, account).map_err(report)? {
+                println!("{}", role);
+            }
+            for chained in app.metadata.get(&account_id).map(|metadata| metadata.chained_roles.as_slice()).unwrap_or_default() {
+                println!("{}", chained.label());
+            }
+        }
+        ["credentials", account, role] => {
+            let mut app = connect()?;
+            let account = find_account(&mut app, account)?;
+            let (credentials, context) = role_credentials(&mut app, account, role)?;
+            print!("{}", exporters::Shell.export(&credentials, &context));
+        }
+        ["credential-process", account, role] => {
+            let mut app = connect()?;
+            let account = find_account(&mut app, account)?;
+            let (credentials, context) = role_credentials(&mut app, account, role)?;
+            print!("{}", exporters::Json.export(&credentials, &context));
+        }
+        ["export", account, role, rest @ ..] if rest.iter().filter(|arg| **arg != "--force").count() <= 1 => {
+            let force = rest.contains(&"--force");
+            let profile: Vec<&str> = rest.iter().copied().filter(|arg| *arg != "--force").collect();
+            let mut app = connect()?;
+            let account = find_account(&mut app, account)?;
+            let (credentials, _) = role_credentials(&mut app, account.clone(), role)?;
+            let alias = app.metadata.get(&account.account_id).map(|metadata| metadata.alias.clone()).unwrap_or_default();
+            let profile = match profile.first() {
+                Some(profile) => profile.to_string(),
+                None => profiles::ProfileNaming::from_config(&app.config_options, "credentials_profile", "default").name(&account, role, &alias),
+            };
+            let aws_config_path = app.config_options.options.iter().find(|option| option.name == "aws_config_path").unwrap().clone();
+            let credentials_path = PathBuf::from(&aws_config_path.value).join("credentials");
+            if !force && CredentialsFile::new(&credentials_path).is_unmanaged(&profile).map_err(report)? {
+                return Err(eyre!("[{}] in {} wasn't written by assumer, pass --force to replace it", profile, credentials_path.display()));
+            }
+            sso::export_env_vars(&credentials, &account, aws_config_path, &profile, force).map_err(report)?;
+            println!("Wrote credentials to the [{}] profile", profile);
+        }
+        ["export-as", format, account, role, to @ ..] if to.len() <= 1 => {
+            let exporter = find_exporter(format)?;
+            let mut app = connect()?;
+            let account = find_account(&mut app, account)?;
+            let (credentials, context) = role_credentials(&mut app, account, role)?;
+            let text = exporter.export(&credentials, &context);
+            let preamble = exporter.preamble(&credentials);
+            match to.first().copied().unwrap_or("-") {
+                // Keep stdout to the export itself, so it can be redirected
+                "-" => {
+                    eprint!("{}", preamble);
+                    print!("{}", text);
+                }
+                "clipboard" => copy_to_clipboard(&app, exporter.name(), &text)?,
+                to => {
+                    print!("{}", preamble);
+                    let path = match to {
+                        "file" => exporters::default_path(exporter.as_ref(), &PathBuf::from(app.config_options.value("export_directory"))),
+                        path => PathBuf::from(path),
+                    };
+                    exporters::write_to_file(exporter.as_ref(), &text, &path).map_err(report)?;
+                    eprintln!("Wrote {} to {}", exporter.name(), path.display());
+                }
+            }
+        }
+        ["copy", account, role, format @ ..] if format.len() <= 1 => {
+            let mut app = connect()?;
+            let exporter = find_exporter(&format.first().map_or_else(|| app.config_options.value("export_format"), |f| f.to_string()))?;
+            let account = find_account(&mut app, account)?;
+            let (credentials, context) = role_credentials(&mut app, account, role)?;
+            copy_to_clipboard(&app, exporter.name(), &exporter.export(&credentials, &context))?;
+        }
+        ["console", args @ ..] if !args.is_empty() => {
+            let mut app = connect()?;
+            console(&mut app, args)?;
+        }
+        ["prune", flag @ ..] if matches!(flag, [] | ["--dry-run"] | ["--yes"]) => {
+            let mut app = connect()?;
+            prune(&mut app, flag.first().copied())?;
+        }
+        ["sync-profiles", flag @ ..] if matches!(flag, [] | ["--dry-run"] | ["--yes"]) => {
+            let mut app = connect()?;
+            sync_profiles(&mut app, flag.first().copied())?;
+        }
+        ["migrate-profiles", flag @ ..] if matches!(flag, [] | ["--dry-run"] | ["--yes"]) => {
+            let mut app = connect()?;
+            migrate_profiles(&mut app, flag.first().copied())?;
+        }
+        ["browsers"] => {
+            let mut app = App::default();
+            app.init_config()?;
+            for launcher in browser::all(&app.config_options) {
+                println!("{}\t{}", launcher.name(), launcher.description());
+            }
+        }
+        ["formats"] => {
+            for exporter in exporters::all() {
+                println!("{}\t{}", exporter.name(), exporter.description());
+            }
+        }
+        [clipboard::CLEAR_COMMAND, delay, digest] => {
+            std::thread::sleep(Duration::from_secs(delay.parse().unwrap_or(0)));
+            clipboard::clear(digest, clipboard::Backend::Native).map_err(report)?;
+        }
+        ["help"] | ["--help"] | ["-h"] => println!("{}", USAGE),
+        _ => return Err(eyre!("Unknown command: {}\n\n{}", args.join(" "), USAGE)),
+    }
+
+    Ok(())
+}
+
+fn connect() -> Result<App> {
+    let mut app = App::default();
+    app.init_config()?;
+
+    let start_url = app.config_options.value("start_url");
+    let region = sso::sso_region(&app.config_options);
+    app.aws_config_provider = sso::get_aws_config(&start_url, &region, &mut app, Some(false)).map_err(report)?;
+
+    Ok(app)
+}
+
+/// Looks an account up by ID, alias, name or email. A cached inventory
+/// within `inventory_ttl_minutes` is tried first, so that repeat calls with
+/// cached credentials don't need the network at all; otherwise the accounts
+/// are listed from SSO.
+fn find_account(app: &mut App, query: &str) -> Result<AccountInfo> {
+    let cached = app.inventory_cache().get_cached_inventory().ok()
+        .filter(|inventory| inventory.is_fresh(app.inventory_ttl()))
+        .map(|inventory| inventory.accounts)
+        .unwrap_or_default();
+    if let Some(account) = match_account(app, &cached, query) {
+        return Ok(account);
+    }
+
+    let accounts = sso::get_sso_accounts(app).map_err(report)?;
+    match_account(app, &accounts, query).ok_or_else(|| eyre!("No account matching {}", query))
+}
+
+fn match_account(app: &App, accounts: &[AccountInfo], query: &str) -> Option<AccountInfo> {
+    let aliased = app.metadata.find_by_alias(query).unwrap_or_default();
+    accounts.iter()
+        .find(|account| account.account_id == query)
+        .or_else(|| accounts.iter().find(|account| account.account_id == aliased))
+        .or_else(|| accounts.iter().find(|account| account.account_name.eq_ignore_ascii_case(query)))
+        .or_else(|| accounts.iter().find(|account| account.email.eq_ignore_ascii_case(query)))
+        .cloned()
+}
+
+/// Every account with its roles, fetched the same way as the UI's
+/// inventory sync. Like the UI, a failed role listing doesn't stop the
+/// sync: it is warned about, and the account is left out and returned
+/// separately.
+fn inventory(app: &App) -> Result<(Vec<AccountInfo>, Vec<AccountInfo>)> {
+    let config_provider = app.aws_config_provider.clone();
+    let concurrency = app.config_options.value("role_prefetch_concurrency").parse().unwrap_or(8);
+    let events = jobs::spawn(move |sender| sso::sync_inventory(config_provider, concurrency, sender));
+
+    let mut accounts: Vec<AccountInfo> = vec![];
+    let mut skipped = vec![];
+    for event in events {
+        match event {
+            JobEvent::Accounts(result) => accounts = result.map_err(report)?,
+            JobEvent::AccountRoles { account_id, roles } => {
+                let Some(i) = accounts.iter().position(|account| account.account_id == account_id) else { continue };
+                match roles {
+                    Ok(roles) => accounts[i].roles = roles,
+                    Err(err) => {
+                        eprintln!("Warning: could not list the roles in {} ({}): {:#}", accounts[i].account_name, account_id, err);
+                        skipped.push(accounts.remove(i));
+                    }
+                }
+            }
+            JobEvent::InventorySyncFinished => break,
+        }
+    }
+    Ok((accounts, skipped))
+}
+
+/// Gets credentials for an SSO role, or assumes a chained role on top of one,
+/// warning on stderr when they are close to expiry.
+fn role_credentials(app: &mut App, account: AccountInfo, role: &str) -> Result<(RoleCredentials, ExportContext)> {
+    let context = ExportContext {
+        account_id: account.account_id.clone(),
+        account_name: account.account_name.clone(),
+        role: role.to_string(),
+        region: sso::working_region(&app.config_options, &app.metadata, &account.account_id, role),
+    };
+    let credentials = match app.metadata.chained_role(&account.account_id, role).cloned() {
+        Some(chained) => {
+            let mfa_code = match &chained.mfa_serial {
+                Some(mfa_serial) if app.credential_cache.get(&account.account_id, &chained.cache_key()).is_none() => {
+                    Some(prompt(&format!("MFA code for {}:", mfa_serial))?)
+                }
+                _ => None,
+            };
+            sso::get_chained_role_credentials(app, account, &chained, mfa_code.as_deref()).map_err(report)?
+        }
+        None => sso::get_account_role_credentials(app, account, role).map_err(report)?,
+    };
+    if credentials.expires_within(app.expiry_warning()) {
+        eprintln!("Warning: credentials are close to expiry ({})", credentials.expiry_countdown());
+    }
+    Ok((credentials, context))
+}
+
+/// Runs `console`, with the `--print`, `--copy` or `--link` flag anywhere
+/// among `args`.
+fn console(app: &mut App, args: &[&str]) -> Result<()> {
+    let mode = args.iter().rev().find_map(|arg| console::Mode::from_flag(arg)).unwrap_or(console::Mode::Open);
+    let args: Vec<&str> = args.iter().copied().filter(|arg| console::Mode::from_flag(arg).is_none()).collect();
+    match args.as_slice() {
+        ["--arn", arn, options @ ..] => open_arn(app, arn, options, mode),
+        [account, role, destination @ ..] if destination.len() <= 1 => {
+            let account = find_account(app, account)?;
+            let destination = match destination.first() {
+                Some(destination) => {
+                    let region = sso::working_region(&app.config_options, &app.metadata, &account.account_id, role);
+                    console::destination_url(destination, &region, sso::partition(&app.config_options))
+                }
+                None => console::destination(&app.config_options, &app.metadata, &account.account_id, role),
+            };
+            open_console(app, account, role, &destination, mode)
+        }
+        _ => Err(eyre!("Unknown command: console {}\n\n{}", args.join(" "), USAGE)),
+    }
+}
+
+/// Signs in to the console as `role` at `destination`, then opens, prints
+/// or copies the sign-in URL.
+fn open_console(app: &mut App, account: AccountInfo, role: &str, destination: &str, mode: console::Mode) -> Result<()> {
+    let session = console::session(&app.config_options, &app.metadata, &account.account_id, role).map_err(report)?;
+    let (credentials, _) = role_credentials(app, account.clone(), role)?;
+    let federation_endpoint = sso::federation_endpoint(&app.config_options);
+    if mode == console::Mode::Open {
+        let launcher = browser::from_config(&app.config_options).map_err(report)?;
+        sso::open_console(credentials, account, role, &federation_endpoint, destination, &session, launcher.as_ref()).map_err(report)?;
+        eprintln!("Opened {}", destination);
+        return Ok(());
+    }
+
+    let url = sso::console_url(&credentials, &federation_endpoint, destination, &session).map_err(report)?;
+    match mode {
+        console::Mode::Print => println!("{}", url),
+        console::Mode::Link => println!("{}", console::hyperlink(&url, &format!("AWS console: {} as {}", account.account_name, role))),
+        _ => copy_to_clipboard(app, "the console sign-in URL", &url)?,
+    }
+    eprintln!("The URL signs in to {} for the next 15 minutes", destination);
+    Ok(())
+}
+
+/// Opens the console at the resource `arn` names, in the ARN's account or
+/// the one given with `--account`, as `--role` or the preferred role.
+fn open_arn(app: &mut App, arn: &str, options: &[&str], mode: console::Mode) -> Result<()> {
+    let arn = console::Arn::parse(arn).map_err(report)?;
+    arn.check_partition(sso::partition(&app.config_options)).map_err(report)?;
+    let (mut account, mut role) = (None, None);
+    let mut options = options.iter();
+    while let Some(option) = options.next() {
+        match (*option, options.next()) {
+            ("--account", Some(value)) => account = Some(*value),
+            ("--role", Some(value)) => role = Some(value.to_string()),
+            _ => return Err(eyre!("Unknown option for console --arn: {}\n\n{}", option, USAGE)),
+        }
+    }
+
+    let query = match account {
+        Some(account) => account.to_string(),
+        None if !arn.account_id.is_empty() => arn.account_id.clone(),
+        None => return Err(eyre!("{} doesn't name an account, pass one with --account", arn)),
+    };
+    let account = find_account(app, &query)?;
+    if !arn.account_id.is_empty() && arn.account_id != account.account_id {
+        return Err(eyre!("{} belongs to account {}, not {}", arn, arn.account_id, account.account_id));
+    }
+    let role = match role {
+        Some(role) => role,
+        None => {
+            let mut roles = sso::get_account_roles(app, account.clone()).map_err(report)?;
+            let chained_roles = app.metadata.get(&account.account_id).map(|metadata| metadata.chained_roles.as_slice()).unwrap_or_default();
+            roles.extend(chained_roles.iter().map(|chained| chained.label()));
+            console::preferred_role(&roles, &app.config_options).ok_or_else(|| eyre!("You have no roles in account {}", account.account_id))?
+        }
+    };
+    let region = sso::working_region(&app.config_options, &app.metadata, &account.account_id, &role);
+    let destination = console::arn_url(&arn, &region);
+    open_console(app, account, &role, &destination, mode)
+}
+
+/// Lists the assumer-owned profiles with their status, then removes the
+/// stale ones once the diff has been confirmed.
+fn prune(app: &mut App, flag: Option<&str>) -> Result<()> {
+    let aws_config_path = PathBuf::from(app.config_options.value("aws_config_path"));
+    let mut managed = profiles::find_managed_profiles(&aws_config_path).map_err(report)?;
+    if managed.is_empty() {
+        println!("No assumer profiles found");
+        return Ok(());
+    }
+
+    let mut accounts = sso::get_sso_accounts(app).map_err(report)?;
+    for account in accounts.iter_mut() {
+        if managed.iter().any(|profile| profile.account_id == account.account_id) {
+            account.roles = sso::get_account_roles(app, account.clone()).map_err(report)?;
+        }
+    }
+    profiles::classify(&mut managed, &accounts, &app.metadata);
+    for profile in &managed {
+        println!("{}\t[{}]\t{}", profile.file.file_name(), profile.section, profile.status);
+    }
+
+    let stale: Vec<_> = managed.iter().filter(|profile| profile.status.is_stale()).collect();
+    if stale.is_empty() {
+        println!("Nothing to prune");
+        return Ok(());
+    }
+    let changes = profiles::plan_removal(&aws_config_path, &stale).map_err(report)?;
+    print!("\n{}", profiles::diff(&changes));
+    if !confirm(flag, &format!("Remove {} profiles?", stale.len()))? {
+        return Ok(());
+    }
+    profiles::apply(&changes).map_err(report)?;
+    println!("Removed {} profiles", stale.len());
+    Ok(())
+}
+
+/// Writes a profile for every role, once the diff has been confirmed.
+/// Accounts whose roles couldn't be listed are skipped and listed at the
+/// end.
+fn sync_profiles(app: &mut App, flag: Option<&str>) -> Result<()> {
+    let (accounts, skipped) = inventory(app)?;
+    write_profiles(app, &accounts, flag)?;
+    if !skipped.is_empty() {
+        let names: Vec<String> = skipped.iter().map(|account| format!("{} ({})", account.account_name, account.account_id)).collect();
+        eprintln!("Skipped {} accounts whose roles could not be listed: {}", skipped.len(), names.join(", "));
+    }
+    Ok(())
+}
+
+fn write_profiles(app: &App, accounts: &[AccountInfo], flag: Option<&str>) -> Result<()> {
+    let aws_config_path = PathBuf::from(app.config_options.value("aws_config_path"));
+    let sync = profiles::plan_sync(&aws_config_path, accounts, &app.metadata, &app.config_options).map_err(report)?;
+    for collision in &sync.collisions {
+        eprintln!("Warning: {}", collision);
+    }
+    if sync.changes.is_empty() {
+        println!("All {} profiles are up to date", sync.profiles.len());
+        return Ok(());
+    }
+    print!("{}", profiles::diff(&sync.changes));
+    if !confirm(flag, &format!("Write {} profiles?", sync.profiles.len()))? {
+        return Ok(());
+    }
+    profiles::apply(&sync.changes).map_err(report)?;
+    println!("Wrote {} profiles", sync.profiles.len());
+    Ok(())
+}
+
+/// Renames the profiles assumer wrote to `~/.aws/config` after the naming
+/// settings change, once the diff has been confirmed.
+fn migrate_profiles(app: &mut App, flag: Option<&str>) -> Result<()> {
+    let aws_config_path = PathBuf::from(app.config_options.value("aws_config_path"));
+    let accounts = sso::get_sso_accounts(app).map_err(report)?;
+    let naming = profiles::ProfileNaming::from_config(&app.config_options, "profile_name_template", profiles::DEFAULT_PROFILE_TEMPLATE);
+    let migration = profiles::plan_migration(&aws_config_path, &accounts, &app.metadata, &naming).map_err(report)?;
+    for collision in &migration.collisions {
+        eprintln!("Warning: {}", collision);
+    }
+    for (from, to) in &migration.renames {
+        match to {
+            Some(to) => println!("[{}] -> [{}]", from, to),
+            None => println!("[{}] -> removed, already exists under its new name", from),
+        }
+    }
+    if migration.changes.is_empty() {
+        println!("Nothing to migrate");
+        return Ok(());
+    }
+    print!("\n{}", profiles::diff(&migration.changes));
+    if !confirm(flag, &format!("Rename {} profiles?", migration.renames.len()))? {
+        return Ok(());
+    }
+    profiles::apply(&migration.changes).map_err(report)?;
+    println!("Migrated {} profiles", migration.renames.len());
+    Ok(())
+}
+
+/// Whether to go ahead after showing a diff: never with `--dry-run`, always
+/// with `--yes`, and otherwise when the user answers `y`.
+fn confirm(flag: Option<&str>, question: &str) -> Result<bool> {
+    match flag {
+        Some("--dry-run") => Ok(false),
+        Some("--yes") => Ok(true),
+        _ => {
+            print!("{} [y/N] ", question);
+            std::io::stdout().flush()?;
+            let mut answer = String::new();
+            std::io::stdin().lock().read_line(&mut answer)?;
+            Ok(answer.trim().eq_ignore_ascii_case("y"))
+        }
+    }
+}
+
+fn find_exporter(format: &str) -> Result<Box<dyn Exporter>> {
+    exporters::find(format).ok_or_else(|| eyre!("Unknown format: {}, see `assumer formats`", format))
+}
+
+fn copy_to_clipboard(app: &App, what: &str, text: &str) -> Result<()> {
+    let backend = clipboard::Backend::from_config(&app.config_options.value("clipboard"));
+    let used = clipboard::copy(text, backend).map_err(report)?;
+    let clear_after = app.config_options.value("clipboard_clear_seconds").parse().unwrap_or(30);
+    if clear_after > 0 && clipboard::clear_later(&clipboard::digest(text), used, Duration::from_secs(clear_after)).is_ok() {
+        eprintln!("Copied {}, clearing in {}s", what, clear_after);
+    } else {
+        eprintln!("Copied {}", what);
+    }
+    Ok(())
+}
+
+/// Asks for a line on stderr, so it doesn't end up in captured output.
+fn prompt(question: &str) -> Result<String> {
+    eprint!("{} ", question);
+    std::io::stderr().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().lock().read_line(&mut answer)?;
+    Ok(answer.trim().to_string())
+}
+
+fn report(err: anyhow::Error) -> Report {
+    eyre!("{:#}", err)
+}
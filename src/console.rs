@@ -0,0 +1,295 @@
+use anyhow::{anyhow, bail, Result};
+use std::fmt;
+use urlencoding::encode;
+use crate::{aws::Partition, metadata::MetadataStore, sso, ConfigOptions};
+
+/// What to do with the federated sign-in URL.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    /// Launch the configured browser.
+    Open,
+    Print,
+    Copy,
+    /// Print an OSC 8 hyperlink, which terminals that support it show as
+    /// clickable text.
+    Link,
+}
+
+impl Mode {
+    /// The mode a command line flag asks for: `--print`, `--copy` or `--link`.
+    pub fn from_flag(flag: &str) -> Option<Mode> {
+        match flag {
+            "--print" => Some(Mode::Print),
+            "--copy" => Some(Mode::Copy),
+            "--link" => Some(Mode::Link),
+            _ => None,
+        }
+    }
+}
+
+/// `text` as an OSC 8 hyperlink to `url`.
+pub fn hyperlink(url: &str, text: &str) -> String {
+    format!("\x1b]8;;{}\x1b\\{}\x1b]8;;\x1b\\", url, text)
+}
+
+/// A console page offered in the destination picker.
+pub struct Service {
+    pub name: &'static str,
+    pub path: &'static str,
+}
+
+/// Common places to land, in the order they're offered.
+pub const SERVICES: &[Service] = &[
+    Service { name: "Console home", path: "console/home" },
+    Service { name: "CloudWatch", path: "cloudwatch/home" },
+    Service { name: "S3", path: "s3/home" },
+    Service { name: "EC2", path: "ec2/home" },
+    Service { name: "IAM", path: "iam/home" },
+    Service { name: "Cost Explorer", path: "costmanagement/home#/cost-explorer" },
+];
+
+/// The URL the federation sign-in redirects to for `destination`. Full
+/// URLs are used as they are. Anything else is a path on the partition's
+/// console such as `cloudwatch/home`, opened in `region`; an empty one is
+/// the console home.
+pub fn destination_url(destination: &str, region: &str, partition: &Partition) -> String {
+    let destination = destination.trim();
+    if destination.starts_with("https://") || destination.starts_with("http://") {
+        return destination.to_string();
+    }
+    let path = destination.trim_start_matches('/');
+    if path.is_empty() || region.is_empty() {
+        return format!("{}{}", partition.console_url, path);
+    }
+    // The region goes in the query, before any `#` route the console uses.
+    let (path, fragment) = match path.split_once('#') {
+        Some((path, fragment)) => (path, format!("#{}", fragment)),
+        None => (path, String::new()),
+    };
+    let separator = if path.contains('?') { '&' } else { '?' };
+    format!("{}{}{}region={}{}", partition.console_url, path, separator, region, fragment)
+}
+
+/// Where the console opens for `role`: its own or its account's destination
+/// from the account details, else the `console_destination` config option,
+/// in the role's working region.
+pub fn destination(config_options: &ConfigOptions, metadata: &MetadataStore, account_id: &str, role: &str) -> String {
+    let destination = metadata.console_destination(account_id, role)
+        .map(String::from)
+        .unwrap_or_else(|| config_options.value("console_destination"));
+    let region = sso::working_region(config_options, metadata, account_id, role);
+    destination_url(&destination, &region, sso::partition(config_options))
+}
+
+/// An Amazon Resource Name, split into its fields. S3 ARNs leave the
+/// region and account empty.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Arn {
+    pub partition: String,
+    pub service: String,
+    pub region: String,
+    pub account_id: String,
+    /// Everything after the account, e.g. `function:my-function` or
+    /// `role/path/name`.
+    pub resource: String,
+}
+
+impl Arn {
+    pub fn parse(text: &str) -> Result<Arn> {
+        let text = text.trim();
+        let fields: Vec<&str> = text.splitn(6, ':').collect();
+        match fields.as_slice() {
+            ["arn", partition, service, region, account_id, resource]
+                if !partition.is_empty() && !service.is_empty() && !resource.is_empty() => {
+                if !account_id.is_empty() && (account_id.len() != 12 || !account_id.chars().all(|c| c.is_ascii_digit())) {
+                    bail!("{} is not an account ID", account_id);
+                }
+                Ok(Arn {
+                    partition: partition.to_string(),
+                    service: service.to_string(),
+                    region: region.to_string(),
+                    account_id: account_id.to_string(),
+                    resource: resource.to_string(),
+                })
+            }
+            _ => bail!("Not an ARN: {}", text),
+        }
+    }
+
+    /// The partition named in the ARN, or else the one of its region.
+    pub fn known_partition(&self) -> &'static Partition {
+        Partition::from_id(&self.partition).unwrap_or_else(|| Partition::from_region(&self.region))
+    }
+
+    /// Fails unless the resource is in `partition`, the one assumer signs
+    /// in to. A console session can't open resources in another partition.
+    pub fn check_partition(&self, partition: &Partition) -> Result<()> {
+        let own = self.known_partition();
+        if own != partition {
+            bail!("{} is in the {} partition, but you sign in to {}", self, own.id, partition.id);
+        }
+        Ok(())
+    }
+
+    /// The console page for the resource, or the service's home page when
+    /// the resource type isn't one assumer knows.
+    pub fn console_path(&self) -> String {
+        let resource = self.resource.as_str();
+        // Resources are `type/id`, `type:id` or, for S3 and SQS, just the name.
+        let (kind, id) = resource.split_once(['/', ':']).unwrap_or(("", resource));
+        let arn = self.to_string();
+        match (self.service.as_str(), kind) {
+            ("s3", _) => match resource.split_once('/') {
+                Some((bucket, key)) => format!("s3/object/{}?prefix={}", bucket, encode(key)),
+                None => format!("s3/buckets/{}", resource),
+            },
+            ("lambda", "function") => {
+                let name = id.split(':').next().unwrap_or(id);
+                format!("lambda/home#/functions/{}", name)
+            }
+            ("ec2", "instance") => format!("ec2/home#InstanceDetails:instanceId={}", id),
+            ("ec2", "security-group") => format!("ec2/home#SecurityGroup:groupId={}", id),
+            ("ec2", "volume") => format!("ec2/home#VolumeDetails:volumeId={}", id),
+            ("ec2", "vpc") => format!("vpcconsole/home#VpcDetails:VpcId={}", id),
+            ("logs", "log-group") => {
+                let name = id.strip_suffix(":*").unwrap_or(id);
+                // The console escapes log group names twice, with `$` for `%`.
+                format!("cloudwatch/home#logsV2:log-groups/log-group/{}", encode(&encode(name)).replace('%', "$"))
+            }
+            ("iam", "role") => format!("iam/home#/roles/details/{}", last_segment(id)),
+            ("iam", "user") => format!("iam/home#/users/details/{}", last_segment(id)),
+            ("iam", "policy") => format!("iam/home#/policies/details/{}", encode(&arn)),
+            ("dynamodb", "table") => format!("dynamodbv2/home#table?name={}", id.split('/').next().unwrap_or(id)),
+            ("sqs", _) => format!("sqs/v3/home#/queues/{}", encode(&format!("https://sqs.{}.{}/{}/{}", self.region, self.known_partition().dns_suffix, self.account_id, resource))),
+            ("sns", _) => format!("sns/v3/home#/topic/{}", arn),
+            ("cloudformation", "stack") => format!("cloudformation/home#/stacks/stackinfo?stackId={}", encode(&arn)),
+            ("ecs", "cluster") => format!("ecs/v2/clusters/{}", id),
+            ("states", "stateMachine") => format!("states/home#/statemachines/view/{}", encode(&arn)),
+            (service, _) => format!("{}/home", service),
+        }
+    }
+}
+
+impl fmt::Display for Arn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "arn:{}:{}:{}:{}:{}", self.partition, self.service, self.region, self.account_id, self.resource)
+    }
+}
+
+fn last_segment(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+/// The role to open ARNs with: the first of the `console_roles` config
+/// option the account has, else the account's first role.
+pub fn preferred_role(roles: &[String], config_options: &ConfigOptions) -> Option<String> {
+    config_options.value("console_roles").split(',')
+        .map(str::trim)
+        .find(|preferred| roles.iter().any(|role| role == preferred))
+        .map(String::from)
+        .or_else(|| roles.first().cloned())
+}
+
+/// The URL to open `arn` at. Resources without a region, such as S3
+/// buckets and IAM roles, open in `working_region`.
+pub fn arn_url(arn: &Arn, working_region: &str) -> String {
+    let region = if arn.region.is_empty() { working_region } else { &arn.region };
+    destination_url(&arn.console_path(), region, arn.known_partition())
+}
+
+/// The shortest console session the federation endpoint accepts.
+pub const MIN_SESSION_SECONDS: u32 = 900;
+/// The longest console session for SSO role credentials.
+pub const MAX_SESSION_SECONDS: u32 = 43200;
+/// The longest console session for credentials from role chaining.
+pub const MAX_CHAINED_SESSION_SECONDS: u32 = 3600;
+
+/// How a console sign-in is set up besides its destination.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Session {
+    pub duration_seconds: u32,
+    /// URL the console's sign-out link returns to, if any.
+    pub issuer: String,
+}
+
+/// The console session for `role`: the role's or account's settings from
+/// the account details, else the `console_session_seconds` and
+/// `console_issuer` config options. Without a duration, sessions last as
+/// long as the credential type allows.
+pub fn session(config_options: &ConfigOptions, metadata: &MetadataStore, account_id: &str, role: &str) -> Result<Session> {
+    let chained = metadata.chained_role(account_id, role).is_some();
+    let max = if chained { MAX_CHAINED_SESSION_SECONDS } else { MAX_SESSION_SECONDS };
+    let duration_seconds = match metadata.console_session_seconds(account_id, role) {
+        Some(seconds) => seconds,
+        None => match config_options.value("console_session_seconds").trim() {
+            "" => max,
+            value => value.parse().map_err(|_| anyhow!("console_session_seconds should be a number of seconds, not {}", value))?,
+        },
+    };
+    if duration_seconds < MIN_SESSION_SECONDS || duration_seconds > max {
+        let kind = if chained { "chained roles" } else { "SSO roles" };
+        bail!("Console sessions for {} last {} to {} seconds, not {}", kind, MIN_SESSION_SECONDS, max, duration_seconds);
+    }
+
+    let issuer = metadata.console_issuer(account_id, role)
+        .map(String::from)
+        .unwrap_or_else(|| config_options.value("console_issuer").trim().to_string());
+    if !issuer.is_empty() && !issuer.starts_with("https://") && !issuer.starts_with("http://") {
+        bail!("The console issuer should be a URL, not {}", issuer);
+    }
+
+    Ok(Session { duration_seconds, issuer })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Arn;
+    use crate::aws::Partition;
+
+    fn console_path(arn: &str) -> String {
+        Arn::parse(arn).unwrap().console_path()
+    }
+
+    #[test]
+    fn rejects_malformed_arns() {
+        assert_eq!(Arn::parse("arn:aws:s3").unwrap_err().to_string(), "Not an ARN: arn:aws:s3");
+        assert_eq!(Arn::parse("not:an:arn:at:all:x").unwrap_err().to_string(), "Not an ARN: not:an:arn:at:all:x");
+        assert_eq!(Arn::parse("arn:aws:iam::12345:role/Admin").unwrap_err().to_string(), "12345 is not an account ID");
+        assert!(Arn::parse("arn:aws:iam::12345678901a:role/Admin").is_err());
+        assert!(Arn::parse("arn:aws:lambda:eu-west-1:111111111111:").is_err());
+    }
+
+    #[test]
+    fn opens_s3_buckets_and_objects() {
+        assert_eq!(console_path("arn:aws:s3:::my-bucket"), "s3/buckets/my-bucket");
+        assert_eq!(console_path("arn:aws:s3:::my-bucket/logs/2024 01.gz"), "s3/object/my-bucket?prefix=logs%2F2024%2001.gz");
+    }
+
+    #[test]
+    fn encodes_log_group_names_twice() {
+        assert_eq!(
+            console_path("arn:aws:logs:eu-west-1:111111111111:log-group:/aws/lambda/my-function:*"),
+            "cloudwatch/home#logsV2:log-groups/log-group/$252Faws$252Flambda$252Fmy-function"
+        );
+    }
+
+    #[test]
+    fn opens_iam_resources_by_name() {
+        assert_eq!(console_path("arn:aws:iam::111111111111:role/service-role/Deployer"), "iam/home#/roles/details/Deployer");
+        assert_eq!(console_path("arn:aws:iam::111111111111:user/ops/alice"), "iam/home#/users/details/alice");
+        assert_eq!(
+            console_path("arn:aws:iam::111111111111:policy/ReadAll"),
+            "iam/home#/policies/details/arn%3Aaws%3Aiam%3A%3A111111111111%3Apolicy%2FReadAll"
+        );
+    }
+
+    #[test]
+    fn rejects_resources_in_other_partitions() {
+        let arn = Arn::parse("arn:aws-us-gov:s3:::my-bucket").unwrap();
+        assert!(arn.check_partition(Partition::from_id("aws-us-gov").unwrap()).is_ok());
+        assert_eq!(
+            arn.check_partition(Partition::from_id("aws").unwrap()).unwrap_err().to_string(),
+            "arn:aws-us-gov:s3:::my-bucket is in the aws-us-gov partition, but you sign in to aws"
+        );
+    }
+}
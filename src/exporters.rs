@@ -0,0 +1,118 @@
+pub mod shell;
+pub mod dotenv;
+pub mod json;
+pub mod kubernetes;
+pub mod github_actions;
+pub mod fields;
+
+pub use shell::*;
+pub use dotenv::*;
+pub use json::*;
+pub use kubernetes::*;
+pub use github_actions::*;
+pub use fields::*;
+
+use anyhow::Result;
+use std::{fs::OpenOptions, io::Write, path::{Path, PathBuf}};
+use crate::sso::RoleCredentials;
+
+/// What an exporter knows about the credentials besides the keys themselves.
+#[derive(Clone, Default)]
+pub struct ExportContext {
+    pub account_id: String,
+    pub account_name: String,
+    pub role: String,
+    /// Working region, exported as `AWS_REGION` when set.
+    pub region: String,
+}
+
+/// Turns role credentials into text for some consumer: a shell, a tool's
+/// env file, a manifest...
+pub trait Exporter {
+    /// Short identifier used on the command line and in the format picker.
+    fn name(&self) -> &'static str;
+    fn description(&self) -> &'static str;
+    /// File name suggested when writing the export to disk.
+    fn file_name(&self) -> &'static str;
+    /// Whether writing to a file should append rather than replace it.
+    fn appends(&self) -> bool {
+        false
+    }
+    fn export(&self, credentials: &RoleCredentials, context: &ExportContext) -> String;
+    /// Text to print before the export is written or printed, such as
+    /// commands that keep the secrets out of CI logs.
+    fn preamble(&self, _credentials: &RoleCredentials) -> String {
+        String::new()
+    }
+}
+
+/// Every built-in exporter, in the order they're offered.
+pub fn all() -> Vec<Box<dyn Exporter>> {
+    vec![
+        Box::new(Shell),
+        Box::new(Fish),
+        Box::new(Cmd),
+        Box::new(PowerShell),
+        Box::new(Dotenv),
+        Box::new(DockerEnvFile),
+        Box::new(Json),
+        Box::new(KubernetesSecret),
+        Box::new(GithubActions),
+        Box::new(Field::AccessKeyId),
+        Box::new(Field::SecretAccessKey),
+        Box::new(Field::SessionToken),
+    ]
+}
+
+pub fn find(name: &str) -> Option<Box<dyn Exporter>> {
+    all().into_iter().find(|exporter| exporter.name() == name)
+}
+
+/// The environment variables the SDKs and CLI read credentials and the
+/// region from.
+pub fn env_variables<'a>(credentials: &'a RoleCredentials, context: &'a ExportContext) -> Vec<(&'static str, &'a str)> {
+    let mut variables = vec![
+        ("AWS_ACCESS_KEY_ID", credentials.access_key_id.as_str()),
+        ("AWS_SECRET_ACCESS_KEY", credentials.secret_access_key.as_str()),
+        ("AWS_SESSION_TOKEN", credentials.session_token.as_str()),
+    ];
+    if !context.region.is_empty() {
+        variables.push(("AWS_REGION", context.region.as_str()));
+    }
+    variables
+}
+
+/// One line per environment variable, each formatted by `line`.
+fn env_lines(credentials: &RoleCredentials, context: &ExportContext, line: impl Fn(&str, &str) -> String) -> String {
+    env_variables(credentials, context).iter()
+        .map(|(name, value)| line(name, value) + "\n")
+        .collect()
+}
+
+/// Writes an export to `path`, appending if the exporter asks for it. The
+/// file is only readable by the current user on Unix.
+pub fn write_to_file(exporter: &dyn Exporter, text: &str, path: &Path) -> Result<()> {
+    let mut options = OpenOptions::new();
+    options.create(true);
+    if exporter.appends() {
+        options.append(true);
+    } else {
+        options.write(true).truncate(true);
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)?.write_all(text.as_bytes())?;
+    Ok(())
+}
+
+/// Where [write_to_file] should write when no path is given: the runner's
+/// `$GITHUB_ENV` for GitHub Actions, else the exporter's file name in `dir`.
+pub fn default_path(exporter: &dyn Exporter, dir: &Path) -> PathBuf {
+    match std::env::var_os("GITHUB_ENV") {
+        Some(github_env) if exporter.name() == GithubActions.name() => PathBuf::from(github_env),
+        _ => dir.join(exporter.file_name()),
+    }
+}
@@ -0,0 +1,50 @@
+use crate::sso::RoleCredentials;
+use super::{env_lines, ExportContext, Exporter};
+
+pub struct Dotenv;
+
+impl Exporter for Dotenv {
+    fn name(&self) -> &'static str {
+        "dotenv"
+    }
+
+    fn description(&self) -> &'static str {
+        "A .env file"
+    }
+
+    fn file_name(&self) -> &'static str {
+        ".env"
+    }
+
+    fn export(&self, credentials: &RoleCredentials, context: &ExportContext) -> String {
+        env_lines(credentials, context, |name, value| format!("{}={}", name, dotenv_quote(value)))
+    }
+}
+
+/// `value` as a double-quoted dotenv value, with backslashes, quotes and
+/// newlines escaped.
+fn dotenv_quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n"))
+}
+
+/// Docker takes every character after `=` literally, so unlike [Dotenv]
+/// nothing is quoted.
+pub struct DockerEnvFile;
+
+impl Exporter for DockerEnvFile {
+    fn name(&self) -> &'static str {
+        "docker"
+    }
+
+    fn description(&self) -> &'static str {
+        "docker run --env-file"
+    }
+
+    fn file_name(&self) -> &'static str {
+        "aws-credentials.env"
+    }
+
+    fn export(&self, credentials: &RoleCredentials, context: &ExportContext) -> String {
+        env_lines(credentials, context, |name, value| format!("{}={}", name, value))
+    }
+}
@@ -0,0 +1,39 @@
+use crate::sso::RoleCredentials;
+use super::{ExportContext, Exporter};
+
+/// A single credential value on its own.
+pub enum Field {
+    AccessKeyId,
+    SecretAccessKey,
+    SessionToken,
+}
+
+impl Exporter for Field {
+    fn name(&self) -> &'static str {
+        match self {
+            Field::AccessKeyId => "access-key-id",
+            Field::SecretAccessKey => "secret-access-key",
+            Field::SessionToken => "session-token",
+        }
+    }
+
+    fn description(&self) -> &'static str {
+        match self {
+            Field::AccessKeyId => "Access key ID only",
+            Field::SecretAccessKey => "Secret access key only",
+            Field::SessionToken => "Session token only",
+        }
+    }
+
+    fn file_name(&self) -> &'static str {
+        self.name()
+    }
+
+    fn export(&self, credentials: &RoleCredentials, _context: &ExportContext) -> String {
+        match self {
+            Field::AccessKeyId => credentials.access_key_id.clone(),
+            Field::SecretAccessKey => credentials.secret_access_key.clone(),
+            Field::SessionToken => credentials.session_token.clone(),
+        }
+    }
+}
@@ -0,0 +1,34 @@
+use crate::sso::RoleCredentials;
+use super::{env_lines, ExportContext, Exporter};
+
+/// Lines for the `$GITHUB_ENV` file, which makes them environment variables
+/// for the following steps of a GitHub Actions job.
+pub struct GithubActions;
+
+impl Exporter for GithubActions {
+    fn name(&self) -> &'static str {
+        "github-actions"
+    }
+
+    fn description(&self) -> &'static str {
+        "GitHub Actions $GITHUB_ENV lines"
+    }
+
+    fn file_name(&self) -> &'static str {
+        "github-env"
+    }
+
+    fn appends(&self) -> bool {
+        true
+    }
+
+    fn export(&self, credentials: &RoleCredentials, context: &ExportContext) -> String {
+        env_lines(credentials, context, |name, value| format!("{}={}", name, value))
+    }
+
+    /// Actions shows the variables set through `$GITHUB_ENV` in the log of
+    /// every later step, so the secrets are masked first.
+    fn preamble(&self, credentials: &RoleCredentials) -> String {
+        format!("::add-mask::{}\n::add-mask::{}\n", credentials.secret_access_key, credentials.session_token)
+    }
+}
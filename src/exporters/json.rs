@@ -0,0 +1,31 @@
+use chrono::SecondsFormat;
+use crate::sso::RoleCredentials;
+use super::{ExportContext, Exporter};
+
+/// The JSON the AWS CLI and SDKs expect from a `credential_process`.
+pub struct Json;
+
+impl Exporter for Json {
+    fn name(&self) -> &'static str {
+        "json"
+    }
+
+    fn description(&self) -> &'static str {
+        "JSON, as returned by a credential_process"
+    }
+
+    fn file_name(&self) -> &'static str {
+        "aws-credentials.json"
+    }
+
+    fn export(&self, credentials: &RoleCredentials, _context: &ExportContext) -> String {
+        let output = serde_json::json!({
+            "Version": 1,
+            "AccessKeyId": credentials.access_key_id,
+            "SecretAccessKey": credentials.secret_access_key,
+            "SessionToken": credentials.session_token,
+            "Expiration": credentials.expiration.to_rfc3339_opts(SecondsFormat::Secs, true),
+        });
+        format!("{}\n", output)
+    }
+}
@@ -0,0 +1,76 @@
+use chrono::SecondsFormat;
+use crate::sso::RoleCredentials;
+use super::{env_variables, ExportContext, Exporter};
+
+/// An Opaque `Secret` manifest with the credentials as `stringData`, ready
+/// for `kubectl apply -f`.
+pub struct KubernetesSecret;
+
+impl KubernetesSecret {
+    /// A DNS-1123 compatible name derived from the account and role.
+    fn secret_name(context: &ExportContext) -> String {
+        let raw = format!("aws-credentials-{}-{}", context.account_id, context.role).to_lowercase();
+        let mut name = String::new();
+        for c in raw.chars() {
+            let c = if c.is_ascii_alphanumeric() { c } else { '-' };
+            if !(c == '-' && name.ends_with('-')) {
+                name.push(c);
+            }
+        }
+        name.chars().take(253).collect::<String>().trim_matches('-').to_string()
+    }
+}
+
+/// `value` as a YAML double-quoted scalar.
+fn quote(value: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+impl Exporter for KubernetesSecret {
+    fn name(&self) -> &'static str {
+        "k8s-secret"
+    }
+
+    fn description(&self) -> &'static str {
+        "Kubernetes Secret manifest"
+    }
+
+    fn file_name(&self) -> &'static str {
+        "aws-credentials-secret.yaml"
+    }
+
+    fn export(&self, credentials: &RoleCredentials, context: &ExportContext) -> String {
+        let mut manifest = format!(
+            "apiVersion: v1\n\
+             kind: Secret\n\
+             metadata:\n  \
+               name: {}\n  \
+               annotations:\n    \
+                 assumer/account: {}\n    \
+                 assumer/role: {}\n    \
+                 assumer/expires: {}\n\
+             type: Opaque\n\
+             stringData:\n",
+            Self::secret_name(context),
+            quote(&format!("{} ({})", context.account_name, context.account_id)),
+            quote(&context.role),
+            quote(&credentials.expiration.to_rfc3339_opts(SecondsFormat::Secs, true)),
+        );
+        for (name, value) in env_variables(credentials, context) {
+            manifest.push_str(&format!("  {}: {}\n", name, quote(value)));
+        }
+        manifest
+    }
+}
@@ -0,0 +1,130 @@
+use crate::sso::RoleCredentials;
+use super::{env_lines, ExportContext, Exporter};
+
+pub struct Shell;
+
+impl Exporter for Shell {
+    fn name(&self) -> &'static str {
+        "shell"
+    }
+
+    fn description(&self) -> &'static str {
+        "POSIX shell exports (bash, zsh)"
+    }
+
+    fn file_name(&self) -> &'static str {
+        "aws-credentials.sh"
+    }
+
+    fn export(&self, credentials: &RoleCredentials, context: &ExportContext) -> String {
+        env_lines(credentials, context, |name, value| format!("export {}={}", name, posix_quote(value)))
+    }
+}
+
+pub struct Fish;
+
+impl Exporter for Fish {
+    fn name(&self) -> &'static str {
+        "fish"
+    }
+
+    fn description(&self) -> &'static str {
+        "fish shell variables"
+    }
+
+    fn file_name(&self) -> &'static str {
+        "aws-credentials.fish"
+    }
+
+    fn export(&self, credentials: &RoleCredentials, context: &ExportContext) -> String {
+        env_lines(credentials, context, |name, value| format!("set -gx {} {}", name, fish_quote(value)))
+    }
+}
+
+pub struct Cmd;
+
+impl Exporter for Cmd {
+    fn name(&self) -> &'static str {
+        "cmd"
+    }
+
+    fn description(&self) -> &'static str {
+        "Windows command prompt"
+    }
+
+    fn file_name(&self) -> &'static str {
+        "aws-credentials.cmd"
+    }
+
+    fn export(&self, credentials: &RoleCredentials, context: &ExportContext) -> String {
+        env_lines(credentials, context, cmd_set)
+    }
+}
+
+pub struct PowerShell;
+
+impl Exporter for PowerShell {
+    fn name(&self) -> &'static str {
+        "powershell"
+    }
+
+    fn description(&self) -> &'static str {
+        "PowerShell environment variables"
+    }
+
+    fn file_name(&self) -> &'static str {
+        "aws-credentials.ps1"
+    }
+
+    fn export(&self, credentials: &RoleCredentials, context: &ExportContext) -> String {
+        env_lines(credentials, context, |name, value| format!("$Env:{} = {}", name, powershell_quote(value)))
+    }
+}
+
+/// `value` as a single-quoted PowerShell string, which expands nothing.
+/// PowerShell also ends these strings at typographic single quotes, so
+/// those are doubled as well.
+fn powershell_quote(value: &str) -> String {
+    let mut quoted = String::from("'");
+    for c in value.chars() {
+        if matches!(c, '\'' | '\u{2018}' | '\u{2019}' | '\u{201a}' | '\u{201b}') {
+            quoted.push(c);
+        }
+        quoted.push(c);
+    }
+    quoted.push('\'');
+    quoted
+}
+
+/// Whether every shell reads `value` as it is, so it can go unquoted. Keys
+/// and tokens are base64, which always is.
+fn is_plain(value: &str) -> bool {
+    !value.is_empty() && value.chars().all(|c| c.is_ascii_alphanumeric() || "_-+=/.:,@".contains(c))
+}
+
+/// `value` as a single-quoted POSIX shell word, where only `'` needs
+/// escaping.
+fn posix_quote(value: &str) -> String {
+    if is_plain(value) {
+        return value.to_string();
+    }
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// `value` as a single-quoted fish word, where `\` and `'` are escaped
+/// with a backslash.
+fn fish_quote(value: &str) -> String {
+    if is_plain(value) {
+        return value.to_string();
+    }
+    format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+/// A `set` command for a batch file. Quoting the whole assignment keeps
+/// `&`, `|`, `<` and `>` literal, and `%` is doubled so it isn't expanded.
+fn cmd_set(name: &str, value: &str) -> String {
+    if is_plain(value) {
+        return format!("set {}={}", name, value);
+    }
+    format!("set \"{}={}\"", name, value.replace('%', "%%"))
+}
@@ -0,0 +1,29 @@
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use crate::aws::AccountInfo;
+
+/// Messages sent from background jobs back to the UI thread. The UI drains
+/// them between frames, so each one should be cheap to apply.
+pub enum JobEvent {
+    /// The account list fetched by an inventory sync.
+    Accounts(Result<Vec<AccountInfo>, anyhow::Error>),
+    /// Roles fetched for a single account by an inventory sync.
+    AccountRoles {
+        account_id: String,
+        roles: Result<Vec<String>, anyhow::Error>,
+    },
+    /// The inventory sync has fetched roles for every account.
+    InventorySyncFinished,
+}
+
+/// Runs `job` on its own thread and returns the receiving end of the channel
+/// it reports through. Dropping the receiver tells the job nobody is
+/// listening anymore; jobs should stop once a send fails.
+pub fn spawn<F>(job: F) -> Receiver<JobEvent>
+where
+    F: FnOnce(Sender<JobEvent>) + Send + 'static,
+{
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || job(sender));
+    receiver
+}
@@ -5,11 +5,25 @@ mod aws;
 mod utils;
 mod widgets;
 mod app;
+mod jobs;
+mod commands;
+mod metadata;
+mod clipboard;
+mod exporters;
+mod profiles;
+mod console;
+mod browser;
 
 use app::*;
 use color_eyre::Result;
 
 fn main() -> Result<()> {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if !args.is_empty() {
+        color_eyre::install()?;
+        return commands::run(&args);
+    }
+
     errors::install_hooks()?;  
     let mut terminal = tui::init()?;
     App::default().run(&mut terminal)?;
@@ -0,0 +1,225 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs, path::{Path, PathBuf}};
+use crate::utils::json;
+
+/// Local, user-maintained details about an account that SSO doesn't know
+/// about.
+#[derive(Serialize, Deserialize, Default, Clone, Debug, PartialEq)]
+pub struct AccountMetadata {
+    #[serde(default)]
+    pub alias: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub note: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub chained_roles: Vec<ChainedRole>,
+    /// Working region for the account's roles, instead of the `region`
+    /// config option.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub region: String,
+    /// Working regions of single roles, overriding `region`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub role_regions: BTreeMap<String, String>,
+    /// Console page to land on, instead of the `console_destination` config
+    /// option: a full URL or a path such as `cloudwatch/home`.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub console_destination: String,
+    /// Console pages of single roles, overriding `console_destination`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub role_console_destinations: BTreeMap<String, String>,
+    /// Console session length, instead of the `console_session_seconds`
+    /// config option.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub console_session_seconds: Option<u32>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub role_console_session_seconds: BTreeMap<String, u32>,
+    /// URL the console's sign-out link returns to, instead of the
+    /// `console_issuer` config option.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub console_issuer: String,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub role_console_issuers: BTreeMap<String, String>,
+}
+
+/// An IAM role assumed through STS on top of the credentials of one of the
+/// account's SSO roles.
+#[derive(Serialize, Deserialize, Default, Clone, Debug, PartialEq)]
+pub struct ChainedRole {
+    pub name: String,
+    /// The SSO role whose credentials call `sts:AssumeRole`.
+    pub source_role: String,
+    pub role_arn: String,
+    /// Template for the role session name. Supports `{account_id}`,
+    /// `{account_name}`, `{role}` (the source role), `{name}` and `{user}`.
+    #[serde(default = "default_session_name")]
+    pub session_name: String,
+    #[serde(default)]
+    pub duration_seconds: Option<i32>,
+    #[serde(default)]
+    pub external_id: Option<String>,
+    /// ARN or serial number of the MFA device the role's trust policy
+    /// requires. Assuming the role then asks for a token code.
+    #[serde(default)]
+    pub mfa_serial: Option<String>,
+    /// Session tags passed to `AssumeRole`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tags: BTreeMap<String, String>,
+}
+
+fn default_session_name() -> String {
+    "assumer-{user}".to_string()
+}
+
+impl ChainedRole {
+    /// How the role shows up next to the account's SSO roles.
+    pub fn label(&self) -> String {
+        format!("{} > {}", self.source_role, self.name)
+    }
+
+    /// What its credentials are cached under: the label, plus whatever
+    /// decides the credentials assuming it gives, so that editing the role
+    /// doesn't keep serving the old role's credentials.
+    pub fn cache_key(&self) -> String {
+        let tags: Vec<String> = self.tags.iter().map(|(key, value)| format!("{}={}", key, value)).collect();
+        format!("{}|{}|{}|{}", self.label(), self.role_arn, self.external_id.as_deref().unwrap_or_default(), tags.join(","))
+    }
+
+    /// Expands the session name template and replaces anything STS doesn't
+    /// accept in a role session name.
+    pub fn session_name(&self, account_id: &str, account_name: &str) -> String {
+        let user = std::env::var("USER")
+            .or_else(|_| std::env::var("USERNAME"))
+            .unwrap_or_else(|_| "user".to_string());
+        let expanded = self.session_name
+            .replace("{account_id}", account_id)
+            .replace("{account_name}", account_name)
+            .replace("{role}", &self.source_role)
+            .replace("{name}", &self.name)
+            .replace("{user}", &user);
+        let mut session_name: String = expanded.chars()
+            .map(|c| if c.is_ascii_alphanumeric() || "+=,.@_-".contains(c) { c } else { '-' })
+            .take(64)
+            .collect();
+        while session_name.len() < 2 {
+            session_name.push('-');
+        }
+        session_name
+    }
+}
+
+impl AccountMetadata {
+    pub fn is_empty(&self) -> bool {
+        self.alias.is_empty() && self.tags.is_empty() && self.note.is_empty() && self.chained_roles.is_empty()
+            && self.region.is_empty() && self.role_regions.is_empty()
+            && self.console_destination.is_empty() && self.role_console_destinations.is_empty()
+            && self.console_session_seconds.is_none() && self.role_console_session_seconds.is_empty()
+            && self.console_issuer.is_empty() && self.role_console_issuers.is_empty()
+    }
+
+    /// Case-insensitive match of `query` against the alias, tags and note.
+    pub fn matches(&self, query: &str) -> bool {
+        let query = query.to_lowercase();
+        self.alias.to_lowercase().contains(&query)
+            || self.tags.iter().any(|tag| tag.to_lowercase().contains(&query))
+            || self.note.to_lowercase().contains(&query)
+    }
+}
+
+/// Account metadata keyed by account ID, kept in a single JSON file.
+#[derive(Default, Clone)]
+pub struct MetadataStore {
+    file_path: PathBuf,
+    accounts: BTreeMap<String, AccountMetadata>,
+}
+
+impl MetadataStore {
+    /// Loads the store, starting empty if the file doesn't exist yet.
+    pub fn load(file_path: &Path) -> Result<Self> {
+        let accounts = if file_path.exists() {
+            json::read_from_file(file_path)?
+        } else {
+            BTreeMap::new()
+        };
+        Ok(Self {
+            file_path: file_path.to_path_buf(),
+            accounts,
+        })
+    }
+
+    pub fn get(&self, account_id: &str) -> Option<&AccountMetadata> {
+        self.accounts.get(account_id)
+    }
+
+    pub fn set(&mut self, account_id: &str, metadata: AccountMetadata) -> Result<()> {
+        if metadata.is_empty() {
+            self.accounts.remove(account_id);
+        } else {
+            self.accounts.insert(account_id.to_string(), metadata);
+        }
+        if let Some(parent) = self.file_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        json::write_to_file(&self.file_path, &self.accounts)
+    }
+
+    /// Finds a chained role of the account by its name or its label.
+    pub fn chained_role(&self, account_id: &str, role: &str) -> Option<&ChainedRole> {
+        self.get(account_id)?.chained_roles.iter()
+            .find(|chained| chained.label() == role || chained.name == role)
+    }
+
+    /// The working region set for `role`, or else for its account.
+    pub fn region(&self, account_id: &str, role: &str) -> Option<&str> {
+        let metadata = self.get(account_id)?;
+        self.role_setting(account_id, role, &metadata.role_regions, &metadata.region)
+    }
+
+    /// The console page to open for `role`, or else for its account.
+    pub fn console_destination(&self, account_id: &str, role: &str) -> Option<&str> {
+        let metadata = self.get(account_id)?;
+        self.role_setting(account_id, role, &metadata.role_console_destinations, &metadata.console_destination)
+    }
+
+    /// How long console sessions for `role` may last, from the role's or
+    /// else the account's settings.
+    pub fn console_session_seconds(&self, account_id: &str, role: &str) -> Option<u32> {
+        let metadata = self.get(account_id)?;
+        self.for_role(account_id, role, &metadata.role_console_session_seconds)
+            .or(metadata.console_session_seconds.as_ref())
+            .copied()
+    }
+
+    /// Where the console's sign-out returns to for `role`, or else for its
+    /// account.
+    pub fn console_issuer(&self, account_id: &str, role: &str) -> Option<&str> {
+        let metadata = self.get(account_id)?;
+        self.role_setting(account_id, role, &metadata.role_console_issuers, &metadata.console_issuer)
+    }
+
+    /// A setting from `by_role` for `role`, falling back to the account's
+    /// `fallback`. Empty values count as unset.
+    fn role_setting<'a>(&self, account_id: &str, role: &str, by_role: &'a BTreeMap<String, String>, fallback: &'a str) -> Option<&'a str> {
+        self.for_role(account_id, role, by_role)
+            .map(String::as_str)
+            .or(Some(fallback))
+            .filter(|value| !value.is_empty())
+    }
+
+    /// The entry of `by_role` for `role`. A chained role can be referred to
+    /// by its name or its label.
+    fn for_role<'a, T>(&self, account_id: &str, role: &str, by_role: &'a BTreeMap<String, T>) -> Option<&'a T> {
+        let chained = self.chained_role(account_id, role);
+        by_role.iter()
+            .find(|(name, _)| *name == role || chained.is_some_and(|chained| **name == chained.name || **name == chained.label()))
+            .map(|(_, value)| value)
+    }
+
+    /// Finds the account ID whose alias is exactly `alias`, ignoring case.
+    pub fn find_by_alias(&self, alias: &str) -> Option<&str> {
+        self.accounts.iter()
+            .find(|(_, metadata)| !metadata.alias.is_empty() && metadata.alias.eq_ignore_ascii_case(alias))
+            .map(|(account_id, _)| account_id.as_str())
+    }
+}
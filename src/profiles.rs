@@ -0,0 +1,469 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use std::{fmt, path::{Path, PathBuf}};
+use crate::{aws::{self, AccountInfo, MANAGED_KEY}, metadata::MetadataStore, sso, utils::{diff::unified_diff, ini_file::IniFile, mask}, ConfigOptions};
+
+/// Used when `profile_name_template` is left empty.
+pub const DEFAULT_PROFILE_TEMPLATE: &str = "{account_name}-{role}";
+
+/// Builds profile names from a template such as `{alias}-{role}`, then
+/// applies the configured case and replaces anything the AWS CLI's
+/// `--profile` would trip over.
+#[derive(Clone, Debug)]
+pub struct ProfileNaming {
+    pub template: String,
+    /// `keep`, `lower` or `upper`.
+    pub case: String,
+    /// Stands in for each run of characters other than letters, digits,
+    /// `-`, `_` and `.`.
+    pub replacement: String,
+    /// The `{sso}` placeholder, e.g. `sso-example` for
+    /// `https://example.awsapps.com/start`.
+    pub sso: String,
+}
+
+impl ProfileNaming {
+    /// Naming from the config, using the template in `template_option` or
+    /// `fallback` when that is empty.
+    pub fn from_config(options: &ConfigOptions, template_option: &str, fallback: &str) -> Self {
+        let template = options.value(template_option);
+        let start_url = options.value("start_url");
+        Self {
+            template: if template.trim().is_empty() { fallback.to_string() } else { template.trim().to_string() },
+            case: options.value("profile_name_case"),
+            replacement: options.value("profile_name_replacement"),
+            sso: if start_url.contains('.') { aws::session_name(&start_url) } else { String::new() },
+        }
+    }
+
+    /// Expands `{account_name}`, `{account_id}`, `{role}`, `{alias}` (the
+    /// account name when there is no alias) and `{sso}`.
+    pub fn name(&self, account: &AccountInfo, role: &str, alias: &str) -> String {
+        let name = self.template
+            .replace("{account_id}", &account.account_id)
+            .replace("{account_name}", &account.account_name)
+            .replace("{alias}", if alias.is_empty() { &account.account_name } else { alias })
+            .replace("{role}", role)
+            .replace("{sso}", &self.sso);
+        let name = match self.case.trim() {
+            "lower" => name.to_lowercase(),
+            "upper" => name.to_uppercase(),
+            _ => name,
+        };
+        sanitize(&name, &self.replacement)
+    }
+}
+
+/// Replaces each run of characters outside `[A-Za-z0-9._-]` with
+/// `replacement`, and trims it from both ends.
+pub fn sanitize(name: &str, replacement: &str) -> String {
+    let mut sanitized = String::new();
+    let mut replacing = false;
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+            sanitized.push(c);
+            replacing = false;
+        } else if !replacing {
+            sanitized.push_str(replacement);
+            replacing = true;
+        }
+    }
+    if replacement.is_empty() {
+        return sanitized;
+    }
+    let mut sanitized = sanitized.as_str();
+    while let Some(rest) = sanitized.strip_prefix(replacement) {
+        sanitized = rest;
+    }
+    while let Some(rest) = sanitized.strip_suffix(replacement) {
+        sanitized = rest;
+    }
+    sanitized.to_string()
+}
+
+/// Whether a section was written by assumer: it carries [MANAGED_KEY], or
+/// it is a config profile named the way assumer named them before that.
+pub fn is_managed(file: ProfileFile, section: &str, managed: Option<&str>) -> bool {
+    managed == Some("true") || (file == ProfileFile::Config && section.starts_with("profile 'assumer-"))
+}
+
+/// Keys that only one of the profile formats uses, or that older versions
+/// wrote. Whichever of them the current format doesn't set are removed.
+const FORMAT_KEYS: &[&str] = &["sso_session", "sso_start_url", "sso_region", "source_profile"];
+
+/// Whether profiles carry their own `sso_start_url` and `sso_region`, for
+/// tools that don't understand `sso-session` sections yet.
+pub fn legacy_format(options: &ConfigOptions) -> bool {
+    options.value("profile_format").trim() == "legacy"
+}
+
+/// The `[sso-session ...]` section profiles refer to and its settings, or
+/// `None` in the legacy format. It is named like the token cache entry, so
+/// the AWS CLI and assumer share the same token.
+pub fn sso_session_section(options: &ConfigOptions) -> Option<(String, Vec<(&'static str, String)>)> {
+    if legacy_format(options) {
+        return None;
+    }
+    let start_url = options.value("start_url");
+    Some((
+        format!("sso-session {}", aws::session_name(&start_url)),
+        vec![
+            ("sso_start_url", start_url),
+            ("sso_region", sso::sso_region(options)),
+            ("sso_registration_scopes", aws::REGISTRATION_SCOPES.to_string()),
+        ],
+    ))
+}
+
+/// The settings of the SSO profile assumer writes for `role`, working in
+/// `region`.
+pub fn sso_profile_values(account_id: &str, role: &str, region: &str, options: &ConfigOptions) -> Vec<(&'static str, String)> {
+    let mut values = match sso_session_section(options) {
+        Some((session, _)) => vec![
+            ("sso_session", session.trim_start_matches("sso-session ").to_string()),
+        ],
+        None => vec![
+            ("sso_start_url", options.value("start_url")),
+            ("sso_region", sso::sso_region(options)),
+        ],
+    };
+    values.extend([
+        ("sso_account_id", account_id.to_string()),
+        ("sso_role_name", role.to_string()),
+        ("region", region.to_string()),
+        ("output", "json".to_string()),
+        (MANAGED_KEY, "true".to_string()),
+    ]);
+    values
+}
+
+/// The [FORMAT_KEYS] to remove from a profile written with `values`.
+pub fn obsolete_keys(values: &[(&str, String)]) -> Vec<&'static str> {
+    FORMAT_KEYS.iter()
+        .filter(|key| !values.iter().any(|(name, _)| name == *key))
+        .copied()
+        .collect()
+}
+
+/// Which of the two AWS shared files a profile lives in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProfileFile {
+    Config,
+    Credentials,
+}
+
+impl ProfileFile {
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            ProfileFile::Config => "config",
+            ProfileFile::Credentials => "credentials",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProfileStatus {
+    Current,
+    /// The account is no longer returned by `ListAccounts`.
+    AccountGone,
+    /// The account is still there, but the role is not.
+    RoleGone,
+    /// Static credentials whose `x_security_token_expires` has passed.
+    Expired,
+}
+
+impl ProfileStatus {
+    pub fn is_stale(&self) -> bool {
+        *self != ProfileStatus::Current
+    }
+}
+
+impl fmt::Display for ProfileStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = match self {
+            ProfileStatus::Current => "current",
+            ProfileStatus::AccountGone => "account gone",
+            ProfileStatus::RoleGone => "role gone",
+            ProfileStatus::Expired => "expired",
+        };
+        f.write_str(status)
+    }
+}
+
+/// A profile section assumer wrote to `~/.aws/config` or
+/// `~/.aws/credentials`.
+#[derive(Clone, Debug)]
+pub struct ManagedProfile {
+    pub file: ProfileFile,
+    /// The section name as written, e.g. `profile sandbox` or `default`.
+    pub section: String,
+    pub account_id: String,
+    pub role: String,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub status: ProfileStatus,
+}
+
+/// Finds every assumer-owned profile under `aws_config_path`. Profiles
+/// written before they were marked are recognised by their
+/// `profile 'assumer-...` names.
+pub fn find_managed_profiles(aws_config_path: &Path) -> Result<Vec<ManagedProfile>> {
+    let mut profiles = vec![];
+    for file in [ProfileFile::Config, ProfileFile::Credentials] {
+        let ini = IniFile::load(&aws_config_path.join(file.file_name()))?;
+        for section in ini.sections() {
+            if !is_managed(file, section, ini.get(section, MANAGED_KEY)) {
+                continue;
+            }
+            let (account_id, role) = match file {
+                ProfileFile::Config => (ini.get(section, "sso_account_id"), ini.get(section, "sso_role_name")),
+                ProfileFile::Credentials => (ini.get(section, "x_assumer_account_id"), ini.get(section, "x_assumer_role")),
+            };
+            profiles.push(ManagedProfile {
+                file,
+                section: section.to_string(),
+                account_id: account_id.unwrap_or_default().to_string(),
+                role: role.unwrap_or_default().to_string(),
+                expires_at: ini.get(section, "x_security_token_expires")
+                    .and_then(|expires| DateTime::parse_from_rfc3339(expires).ok())
+                    .map(|expires| expires.with_timezone(&Utc)),
+                status: ProfileStatus::Current,
+            });
+        }
+    }
+    Ok(profiles)
+}
+
+/// Whether `section` of the config file is the profile assumer wrote for
+/// `role` in `account_id`.
+fn is_profile_for(ini: &IniFile, section: &str, account_id: &str, role: &str) -> bool {
+    is_managed(ProfileFile::Config, section, ini.get(section, MANAGED_KEY))
+        && ini.get(section, "sso_account_id") == Some(account_id)
+        && ini.get(section, "sso_role_name") == Some(role)
+}
+
+/// Works out each profile's status against the current inventory. Roles of
+/// an account whose roles haven't been fetched are given the benefit of
+/// the doubt.
+pub fn classify(profiles: &mut [ManagedProfile], accounts: &[AccountInfo], metadata: &MetadataStore) {
+    let now = Utc::now();
+    for profile in profiles.iter_mut() {
+        let account = accounts.iter().find(|account| account.account_id == profile.account_id);
+        let role_exists = |account: &AccountInfo| {
+            let role = metadata.chained_role(&account.account_id, &profile.role)
+                .map_or(profile.role.as_str(), |chained| chained.source_role.as_str());
+            account.roles.is_empty() || account.roles.iter().any(|name| name == role)
+        };
+        profile.status = match account {
+            None => ProfileStatus::AccountGone,
+            Some(account) if !role_exists(account) => ProfileStatus::RoleGone,
+            _ if profile.expires_at.is_some_and(|expires_at| expires_at < now) => ProfileStatus::Expired,
+            _ => ProfileStatus::Current,
+        };
+    }
+}
+
+/// The files that removing `profiles` would change: path, current
+/// contents and new contents.
+pub fn plan_removal(aws_config_path: &Path, profiles: &[&ManagedProfile]) -> Result<Vec<(PathBuf, String, String)>> {
+    let mut changes = vec![];
+    for file in [ProfileFile::Config, ProfileFile::Credentials] {
+        let sections: Vec<&str> = profiles.iter()
+            .filter(|profile| profile.file == file)
+            .map(|profile| profile.section.as_str())
+            .collect();
+        if sections.is_empty() {
+            continue;
+        }
+        let path = aws_config_path.join(file.file_name());
+        let mut ini = IniFile::load(&path)?;
+        let current = ini.to_string();
+        for section in sections {
+            ini.remove_section(section);
+        }
+        changes.push((path, current, ini.to_string()));
+    }
+    Ok(changes)
+}
+
+/// Moving the managed profiles in `~/.aws/config` to the names the
+/// current template gives them.
+#[derive(Default)]
+pub struct Migration {
+    /// Old and new section names. Profiles that already exist under their
+    /// new name are dropped, which shows as `None`.
+    pub renames: Vec<(String, Option<String>)>,
+    /// Profiles left alone because their new name is taken by another
+    /// account or role, or by a profile assumer didn't write.
+    pub collisions: Vec<String>,
+    pub changes: Vec<(PathBuf, String, String)>,
+}
+
+pub fn plan_migration(aws_config_path: &Path, accounts: &[AccountInfo], metadata: &MetadataStore, naming: &ProfileNaming) -> Result<Migration> {
+    let path = aws_config_path.join(ProfileFile::Config.file_name());
+    let mut ini = IniFile::load(&path)?;
+    let current = ini.to_string();
+
+    let managed = find_managed_profiles(aws_config_path)?;
+    let mut planned: Vec<(&ManagedProfile, String)> = vec![];
+    for profile in managed.iter().filter(|profile| profile.file == ProfileFile::Config) {
+        let Some(account) = accounts.iter().find(|account| account.account_id == profile.account_id) else { continue };
+        let alias = metadata.get(&account.account_id).map(|metadata| metadata.alias.as_str()).unwrap_or_default();
+        let target = format!("profile {}", naming.name(account, &profile.role, alias));
+        if target != profile.section {
+            planned.push((profile, target));
+        }
+    }
+
+    let mut migration = Migration::default();
+    for (profile, to) in &planned {
+        let exists = ini.has_section(to);
+        let taken = if exists {
+            !is_profile_for(&ini, to, &profile.account_id, &profile.role)
+        } else {
+            planned.iter().any(|(other, other_to)| other_to == to && (other.account_id != profile.account_id || other.role != profile.role))
+        };
+        if taken {
+            migration.collisions.push(format!("[{}] can't become [{}], which is used by another profile", profile.section, to));
+        } else if exists || migration.renames.iter().any(|(_, renamed)| renamed.as_ref() == Some(to)) {
+            migration.renames.push((profile.section.clone(), None));
+        } else {
+            migration.renames.push((profile.section.clone(), Some(to.clone())));
+        }
+    }
+    for (from, to) in &migration.renames {
+        match to {
+            Some(to) => ini.rename_section(from, to),
+            None => ini.remove_section(from),
+        }
+    }
+    if !migration.renames.is_empty() {
+        migration.changes.push((path, current, ini.to_string()));
+    }
+    Ok(migration)
+}
+
+/// Writing a profile for every role of every account to `~/.aws/config`.
+#[derive(Default)]
+pub struct ProfileSync {
+    /// Names of the profiles that end up in the file.
+    pub profiles: Vec<String>,
+    /// Roles left without a profile because their name is taken.
+    pub collisions: Vec<String>,
+    pub changes: Vec<(PathBuf, String, String)>,
+}
+
+/// Plans a profile for each role in `accounts`, named by
+/// `profile_name_template`. Profiles assumer wrote for the same roles under
+/// other names are dropped. A name that two roles end up with, or that
+/// belongs to a profile assumer didn't write, is reported instead.
+pub fn plan_sync(aws_config_path: &Path, accounts: &[AccountInfo], metadata: &MetadataStore, options: &ConfigOptions) -> Result<ProfileSync> {
+    let path = aws_config_path.join(ProfileFile::Config.file_name());
+    let mut ini = IniFile::load(&path)?;
+    let current = ini.to_string();
+    let naming = ProfileNaming::from_config(options, "profile_name_template", DEFAULT_PROFILE_TEMPLATE);
+
+    let mut wanted: Vec<(String, &AccountInfo, &str)> = vec![];
+    for account in accounts {
+        let alias = metadata.get(&account.account_id).map(|metadata| metadata.alias.as_str()).unwrap_or_default();
+        for role in &account.roles {
+            wanted.push((format!("profile {}", naming.name(account, role, alias)), account, role));
+        }
+    }
+
+    let mut sync = ProfileSync::default();
+    let mut planned: Vec<&(String, &AccountInfo, &str)> = vec![];
+    for entry in &wanted {
+        let (name, account, role) = entry;
+        let shared = wanted.iter().filter(|(other, _, _)| other == name).count() > 1;
+        let taken = ini.has_section(name) && !is_profile_for(&ini, name, &account.account_id, role);
+        if shared || taken {
+            sync.collisions.push(format!("{}/{} would be [{}], which is used by another profile", account.account_name, role, name));
+        } else {
+            planned.push(entry);
+        }
+    }
+
+    let previous_names: Vec<String> = ini.sections().into_iter()
+        .filter(|section| {
+            !planned.iter().any(|(name, _, _)| name == section)
+                && planned.iter().any(|(_, account, role)| is_profile_for(&ini, section, &account.account_id, role))
+        })
+        .map(String::from)
+        .collect();
+    for section in previous_names {
+        ini.remove_section(&section);
+    }
+    if let Some((session, values)) = sso_session_section(options).filter(|_| !planned.is_empty()) {
+        for (key, value) in values {
+            ini.set(&session, key, &value);
+        }
+    }
+    for (name, account, role) in planned {
+        let region = sso::working_region(options, metadata, &account.account_id, role);
+        write_sso_profile(&mut ini, name, &account.account_id, role, &region, options);
+        sync.profiles.push(name.clone());
+    }
+    let updated = ini.to_string();
+    if updated != current {
+        sync.changes.push((path, current, updated));
+    }
+    Ok(sync)
+}
+
+/// Sets the settings of `role`'s SSO profile in `section`, dropping those
+/// only the other profile format uses.
+pub fn write_sso_profile(ini: &mut IniFile, section: &str, account_id: &str, role: &str, region: &str, options: &ConfigOptions) {
+    let values = sso_profile_values(account_id, role, region, options);
+    for key in obsolete_keys(&values) {
+        ini.remove_key(section, key);
+    }
+    for (key, value) in values {
+        ini.set(section, key, &value);
+    }
+}
+
+/// Keys whose values are masked in diffs.
+const SECRET_KEYS: &[&str] = &["aws_secret_access_key", "aws_session_token", "aws_security_token"];
+
+/// A unified diff of every change in `changes`, with secret values masked
+/// so that reviewing a change never shows them.
+pub fn diff(changes: &[(PathBuf, String, String)]) -> String {
+    changes.iter()
+        .map(|(path, current, updated)| {
+            let name = path.display().to_string();
+            unified_diff(&redact(current), &redact(updated), &name, &name, 2)
+        })
+        .collect()
+}
+
+/// `contents` with the values of [SECRET_KEYS] masked.
+fn redact(contents: &str) -> String {
+    contents.split_inclusive('\n')
+        .map(|line| {
+            let text = line.trim_end_matches(['\r', '\n']);
+            let newline = &line[text.len()..];
+            match text.split_once('=') {
+                Some((key, value)) if SECRET_KEYS.contains(&key.trim().to_lowercase().as_str()) => {
+                    let spacing = &value[..value.len() - value.trim_start().len()];
+                    format!("{}={}{}{}", key, spacing, mask::mask_secret(value.trim(), 4), newline)
+                }
+                _ => line.to_string(),
+            }
+        })
+        .collect()
+}
+
+/// Writes the planned changes, keeping a backup of each file next to it.
+/// Backups are only readable by the current user, since the credentials
+/// file holds secrets.
+pub fn apply(changes: &[(PathBuf, String, String)]) -> Result<()> {
+    for (path, current, updated) in changes {
+        if path.exists() {
+            let mut backup = path.file_name().unwrap_or_default().to_os_string();
+            backup.push(".assumer-backup");
+            IniFile::parse(current).write_to_private_file(&path.with_file_name(backup))?;
+        }
+        IniFile::parse(updated).write_to_file(path)?;
+    }
+    Ok(())
+}
@@ -1,63 +1,161 @@
 use anyhow::Error;
-use ini::Ini;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use std::{path::PathBuf, process::Command};
-use crate::{aws::{session_name, AccessToken, AccountInfo, AccountInfoProvider, SsoAccessTokenProvider}, App, ConfigOption, ConfigOptions};
+use std::{path::PathBuf, sync::{mpsc::Sender, Arc}};
+use tokio::{sync::Semaphore, task::JoinSet};
+use crate::{browser::{Launcher, Target}, console::Session, utils::{ini_file::IniFile, serde::json_date_format}, aws::{retry_config, session_name, AccessToken, Partition, PARTITIONS, AccountInfo, AccountInfoProvider, AssumeRoleProvider, CredentialsFile, SsoAccessTokenProvider, MANAGED_KEY}, metadata::{ChainedRole, MetadataStore}, jobs::JobEvent, profiles::{self, ProfileFile, ProfileNaming}, App, ConfigOption, ConfigOptions};
 use aws_config::{BehaviorVersion, Region};
+use chrono::{DateTime, Duration, Utc};
 use directories::UserDirs;
-use urlencoding::encode;
 
-#[derive(Default, Clone)]
+#[derive(Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct RoleCredentials {
     pub name: String,
     pub access_key_id: String,
     pub secret_access_key: String,
     pub session_token: String,
-    pub expiration: String,
+    #[serde(with = "json_date_format")]
+    pub expiration: DateTime<Utc>,
+}
+
+impl RoleCredentials {
+    pub fn expires_in(&self) -> Duration {
+        self.expiration - Utc::now()
+    }
+
+    /// Whether the credentials expire within `margin`, or already have.
+    pub fn expires_within(&self, margin: Duration) -> bool {
+        self.expires_in() < margin
+    }
+
+    /// A short description of the time left, e.g. "expires in 1h 5m" or
+    /// "expired 3m ago".
+    pub fn expiry_countdown(&self) -> String {
+        let remaining = self.expires_in();
+        if remaining < Duration::zero() {
+            format!("expired {} ago", format_duration(-remaining))
+        } else {
+            format!("expires in {}", format_duration(remaining))
+        }
+    }
 }
 
-#[derive(Clone)]
+fn format_duration(duration: Duration) -> String {
+    let hours = duration.num_hours();
+    let minutes = duration.num_minutes() % 60;
+    if hours > 0 {
+        format!("{}h {}m", hours, minutes)
+    } else if minutes > 0 {
+        format!("{}m", minutes)
+    } else {
+        format!("{}s", duration.num_seconds())
+    }
+}
+
+#[derive(Clone, Default)]
 pub struct ConfigProvider {
     pub access_token: AccessToken,
     pub account_info_provider: Option<AccountInfoProvider>,
     pub token_provider: Option<SsoAccessTokenProvider>,
+    pub assume_role_provider: Option<AssumeRoleProvider>,
 }
 
-impl Default for ConfigProvider {
-    fn default() -> Self {
-        ConfigProvider {
-            access_token: AccessToken::default(),
-            account_info_provider: None,
-            token_provider: None,
-        }
+/// Reads an endpoint URL override from the config, treating an empty value
+/// as "use the real AWS endpoint".
+pub fn endpoint_override(config_options: &ConfigOptions, name: &str) -> Option<String> {
+    let value = config_options.value(name);
+    if value.trim().is_empty() {
+        None
+    } else {
+        Some(value.trim().to_string())
     }
 }
 
+/// The region IAM Identity Center lives in: `sso_region`, or `region` for
+/// configs written before the two were separate.
+pub fn sso_region(config_options: &ConfigOptions) -> String {
+    let sso_region = config_options.value("sso_region");
+    if sso_region.trim().is_empty() {
+        config_options.value("region")
+    } else {
+        sso_region.trim().to_string()
+    }
+}
+
+/// The region to work in with `role`'s credentials: the role's or account's
+/// own region from the account details, else the `region` config option.
+/// A `region` outside the SSO region's partition, such as the default
+/// `us-east-1` with GovCloud, is replaced by the SSO region.
+pub fn working_region(config_options: &ConfigOptions, metadata: &MetadataStore, account_id: &str, role: &str) -> String {
+    metadata.region(account_id, role)
+        .map(String::from)
+        .unwrap_or_else(|| {
+            let region = config_options.value("region");
+            if Partition::from_region(&region) == partition(config_options) {
+                region
+            } else {
+                sso_region(config_options)
+            }
+        })
+}
+
+/// The partition the SSO accounts live in, going by the SSO region.
+pub fn partition(config_options: &ConfigOptions) -> &'static Partition {
+    Partition::from_region(&sso_region(config_options))
+}
+
+/// The console federation endpoint: `federation_endpoint_url`, or the
+/// partition's own.
+pub fn federation_endpoint(config_options: &ConfigOptions) -> String {
+    endpoint_override(config_options, "federation_endpoint_url")
+        .unwrap_or_else(|| partition(config_options).federation_endpoint())
+}
+
 #[::tokio::main]
 pub async fn get_aws_config(start_url: &str, region: &str, app: &mut App, new_token: Option<bool>) -> Result<ConfigProvider, anyhow::Error> {
     if start_url.is_empty() {
         return Err(Error::msg("SSO Start URL is required"));
     }
+    // The SDK picks the SSO, OIDC and STS endpoints of the region's
+    // partition, so the region has to be in the same one as the portal.
+    let partition = Partition::from_region(region);
+    if !partition.has_start_url(start_url) {
+        if let Some(other) = PARTITIONS.iter().find(|other| other.has_start_url(start_url)) {
+            return Err(Error::msg(format!(
+                "{} is in the {} partition but the SSO region {} is in {}; set sso_region to the region IAM Identity Center runs in",
+                start_url, other.id, region, partition.id,
+            )));
+        }
+    }
     let user_dirs = UserDirs::new().expect("Could not resolve user HOME.");
     let home_dir = user_dirs.home_dir();
     let aws_config_dir = home_dir.join(".aws");
 
+    let max_attempts = app.config_options.value("retry_max_attempts").parse().unwrap_or(5);
+    let max_backoff = app.config_options.value("retry_max_backoff_seconds").parse().unwrap_or(20);
+
     let config = aws_config::SdkConfig::builder()
         .region(Some(Region::new(region.to_string())))
         .behavior_version(BehaviorVersion::latest())
+        .retry_config(retry_config(max_attempts, std::time::Duration::from_secs(max_backoff)))
         .build();
 
-    let session_name = session_name(&start_url);
-    let token_provider = SsoAccessTokenProvider::new(&config, session_name.as_str(), &aws_config_dir)?;
-    let access_token = token_provider.get_access_token(&start_url, new_token.unwrap_or(false), app).await;
+    let sso_endpoint_url = endpoint_override(&app.config_options, "sso_endpoint_url");
+    let oidc_endpoint_url = endpoint_override(&app.config_options, "oidc_endpoint_url");
+    let sts_endpoint_url = endpoint_override(&app.config_options, "sts_endpoint_url");
+
+    let session_name = session_name(start_url);
+    let token_provider = SsoAccessTokenProvider::new(&config, session_name.as_str(), &aws_config_dir, oidc_endpoint_url.as_deref())?;
+    let access_token = token_provider.get_access_token(start_url, new_token.unwrap_or(false), app).await;
 
     match access_token {
         Ok(token) => {
             Ok(ConfigProvider {
                 access_token: token,
-                account_info_provider: Some(AccountInfoProvider::new(&config)),
+                account_info_provider: Some(AccountInfoProvider::new(&config, sso_endpoint_url.as_deref())),
                 token_provider: Some(token_provider),
+                assume_role_provider: Some(AssumeRoleProvider::new(&config, sts_endpoint_url.as_deref())),
             })
         }
         Err(e) => Err(e),
@@ -68,7 +166,7 @@ pub async fn get_aws_config(start_url: &str, region: &str, app: &mut App, new_to
 pub async fn get_sso_accounts(app: &mut App) -> Result<Vec<AccountInfo>, anyhow::Error> {
     let config_provider = app.aws_config_provider.clone();
     let token_provider = &config_provider.token_provider.as_ref().unwrap();
-    let start_url = &app.config_options.options.iter().find(|option| option.name == "start_url").unwrap().value.clone();
+    let start_url = &app.config_options.value("start_url");
     let access_token = token_provider.get_access_token(start_url, false, app).await?;
 
     let mut sso_accounts = config_provider.account_info_provider.as_ref().unwrap()
@@ -92,24 +190,122 @@ pub async fn get_account_roles(app: &mut App, account: AccountInfo) -> Result<Ve
     Ok(roles)
 }
 
+/// Lists every account and then fetches their roles, at most `concurrency`
+/// requests at a time, reporting each result through `sender` as soon as it
+/// arrives. Runs without any user interaction, so it only works with a
+/// cached (or refreshable) token.
 #[::tokio::main]
-pub async fn get_account_role_credentials(app: &mut App, account: AccountInfo, role: &str) -> Result<RoleCredentials, anyhow::Error> {     
+pub async fn sync_inventory(config_provider: ConfigProvider, concurrency: usize, sender: Sender<JobEvent>) {
+    let (access_token, accounts) = match list_sso_accounts(&config_provider).await {
+        Ok(result) => result,
+        Err(err) => {
+            let _ = sender.send(JobEvent::Accounts(Err(err)));
+            return;
+        }
+    };
+    if sender.send(JobEvent::Accounts(Ok(accounts.clone()))).is_err() {
+        return;
+    }
+
+    let semaphore = Arc::new(Semaphore::new(concurrency.max(1)));
+    let provider = Arc::new(config_provider.account_info_provider.unwrap());
+    let access_token = Arc::new(access_token);
+    let mut tasks = JoinSet::new();
+
+    for account in accounts {
+        let semaphore = semaphore.clone();
+        let provider = provider.clone();
+        let access_token = access_token.clone();
+        tasks.spawn(async move {
+            let _permit = semaphore.acquire_owned().await.ok();
+            let roles = provider.get_roles_for_account(&access_token, &account).await;
+            (account.account_id, roles)
+        });
+    }
+
+    while let Some(result) = tasks.join_next().await {
+        let Ok((account_id, roles)) = result else { continue };
+        if sender.send(JobEvent::AccountRoles { account_id, roles }).is_err() {
+            // Nobody is listening anymore, e.g. the config was reloaded.
+            tasks.abort_all();
+            return;
+        }
+    }
+
+    let _ = sender.send(JobEvent::InventorySyncFinished);
+}
+
+async fn list_sso_accounts(config_provider: &ConfigProvider) -> Result<(AccessToken, Vec<AccountInfo>), anyhow::Error> {
+    let (Some(token_provider), Some(account_info_provider)) = (&config_provider.token_provider, &config_provider.account_info_provider) else {
+        return Err(Error::msg("No AWS Config Provider"));
+    };
+    let access_token = if config_provider.access_token.is_expired() {
+        token_provider.get_cached_access_token().await?
+    } else {
+        config_provider.access_token.clone()
+    };
+
+    let mut sso_accounts = account_info_provider
+        .get_account_list(&access_token)
+        .await?;
+
+    sso_accounts.sort();
+
+    Ok((access_token, sso_accounts))
+}
+
+#[::tokio::main]
+pub async fn get_account_role_credentials(app: &mut App, account: AccountInfo, role: &str) -> Result<RoleCredentials, anyhow::Error> {
+    fetch_role_credentials(app, &account, role).await
+}
+
+/// Gets credentials for the SSO role `chained.source_role` and uses them to
+/// assume `chained.role_arn`, with `mfa_code` if the role needs MFA.
+#[::tokio::main]
+pub async fn get_chained_role_credentials(app: &mut App, account: AccountInfo, chained: &ChainedRole, mfa_code: Option<&str>) -> Result<RoleCredentials, anyhow::Error> {
+    let cache_key = chained.cache_key();
+    if let Some(credentials) = app.credential_cache.get(&account.account_id, &cache_key) {
+        return Ok(credentials);
+    }
+
+    let source = fetch_role_credentials(app, &account, &chained.source_role).await?;
+    let Some(assume_role_provider) = app.aws_config_provider.assume_role_provider.clone() else {
+        return Err(Error::msg("No AWS Config Provider"));
+    };
+    let session_name = chained.session_name(&account.account_id, &account.account_name);
+    let credentials = assume_role_provider.assume_role(&source, chained, &session_name, mfa_code).await
+        .map_err(|err| err.context(format!("Could not assume {}", chained.role_arn)))?;
+    let _ = app.credential_cache.cache_credentials(&account.account_id, &cache_key, &credentials);
+
+    Ok(credentials)
+}
+
+async fn fetch_role_credentials(app: &mut App, account: &AccountInfo, role: &str) -> Result<RoleCredentials, anyhow::Error> {
+    if let Some(credentials) = app.credential_cache.get(&account.account_id, role) {
+        return Ok(credentials);
+    }
+
     let config_provider = app.aws_config_provider.clone();
     let token_provider = &config_provider.token_provider.as_ref().unwrap();
     let start_url = &app.config_options.options.iter().find(|option| option.name == "start_url").unwrap().value.clone();
     let access_token = token_provider.get_access_token(start_url, false, app).await?;
 
     // Get credentials for the role
-    let role_credentials_output = config_provider.account_info_provider.unwrap().get_role_credentials(&access_token, &account, role).await?;
+    let role_credentials_output = config_provider.account_info_provider.unwrap().get_role_credentials(&access_token, account, role).await?;
     let role_credentials = role_credentials_output.role_credentials().unwrap();
 
-    Ok( RoleCredentials {
+    let credentials = RoleCredentials {
         name: role.to_string(),
         access_key_id: role_credentials.access_key_id().unwrap().to_string(),
         secret_access_key: role_credentials.secret_access_key().unwrap().to_string(),
         session_token: role_credentials.session_token().unwrap().to_string(),
-        expiration: role_credentials.expiration().to_string(),
-    })
+        expiration: DateTime::from_timestamp_millis(role_credentials.expiration())
+            .ok_or_else(|| Error::msg("GetRoleCredentials returned an invalid expiration"))?,
+    };
+    // A cache that can't be written to only costs us the next call.
+    let _ = app.credential_cache.cache_credentials(&account.account_id, role, &credentials);
+
+    Ok(credentials)
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -120,27 +316,32 @@ struct SessionData {
     session_token: String
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct ContainerUrl {
-    name: String,
-    url: String
+/// Opens the console at `destination` with `launcher`, signed in with the
+/// role's credentials.
+pub fn open_console(role_credentials: RoleCredentials, account: AccountInfo, role: &str, federation_endpoint: &str, destination: &str, session: &Session, launcher: &dyn Launcher) -> Result<(), anyhow::Error> {
+    let federated_url = console_url(&role_credentials, federation_endpoint, destination, session)?;
+    launcher.launch(&federated_url, &Target { account_id: &account.account_id, role })
 }
 
+/// Trades the role's credentials for a sign-in token and returns the
+/// federation URL that signs in to the console at `destination` for the
+/// session's duration. Anyone with the URL can use it until the token
+/// expires, 15 minutes later.
 #[::tokio::main]
-pub async fn open_console(role_credentials: RoleCredentials, account: AccountInfo, role: &str) -> Result<(), anyhow::Error> {
+pub async fn console_url(role_credentials: &RoleCredentials, federation_endpoint: &str, destination: &str, session: &Session) -> Result<String, anyhow::Error> {
     let session_data = SessionData {
         session_id: role_credentials.access_key_id.to_string(),
         session_key: role_credentials.secret_access_key.to_string(),
         session_token: role_credentials.session_token.to_string(),
     };
 
-    let aws_federated_signin_endpoint = "https://signin.aws.amazon.com/federation";
+    let aws_federated_signin_endpoint = federation_endpoint;
     let session_data_json = serde_json::to_string(&session_data)?;
 
 
     let token_params = [
         ("Action", "getSigninToken"), 
-        ("SessionDuration", "43200"),
+        ("SessionDuration", &session.duration_seconds.to_string()),
         ("Session", &session_data_json)
     ];
 
@@ -152,106 +353,78 @@ pub async fn open_console(role_credentials: RoleCredentials, account: AccountInf
 
     let signin_token_resonse = response.text().await?;
     let binding = serde_json::from_str::<serde_json::Value>(&signin_token_resonse)?;
-    let signin_token = binding.get("SigninToken").unwrap().as_str().unwrap();
+    let signin_token = binding.get("SigninToken")
+        .and_then(|token| token.as_str())
+        .ok_or_else(|| Error::msg("The federation endpoint returned no sign-in token"))?;
 
     let federated_params = [
         ("Action", "login"), 
-        ("Issuer", ""),
-        ("Destination", "https://console.aws.amazon.com/"), 
-        ("SigninToken", &signin_token)
+        ("Issuer", session.issuer.as_str()),
+        ("Destination", destination), 
+        ("SigninToken", signin_token)
     ];
 
-    let federated_url = format!("{}?{}", aws_federated_signin_endpoint, serde_urlencoded::to_string(&federated_params)?);     
-    let profile_name = format!("aws-sso-{}-{}", account.account_id, role);
-
-    let granted_container_url = ContainerUrl {
-        name: profile_name.to_string(),
-        url: encode(&federated_url).to_string()
-    };
-
-    let granted_container_oss = format!("ext+granted-containers:name={}&url={}", granted_container_url.name, granted_container_url.url);
-
-    if cfg!(target_os = "windows") {
-        // For Windows
-        Command::new("powershell")
-            .args(&["-Command", "Start-Process", "firefox", "-ArgumentList", &format!("'--new-tab', '{}'", &granted_container_oss)])
-            .status()
-            .expect("failed to open browser");
-    } else if cfg!(target_os = "macos") {
-        // For macOS
-        Command::new("open")
-            .args(&["-na", "Firefox", "--args", "--new-tab",  &granted_container_oss])
-            .status()
-            .expect("failed to open browser");
-    } else if cfg!(target_os = "linux") {
-        // For Linux
-        Command::new("firefox")
-            .args(&["--new-table", &granted_container_oss])
-            .status()
-            .expect("failed to open browser");
-    } else {
-        // Fallback
-        webbrowser::open(&federated_url).expect("failed to open browser");
-    }
+    Ok(format!("{}?{}", aws_federated_signin_endpoint, serde_urlencoded::to_string(federated_params)?))
+}
 
-    Ok(())
+pub fn get_assumer_path() -> PathBuf {
+    let user_dirs = UserDirs::new().expect("Could not find user directories");
+    user_dirs.home_dir().join(".assumer")
 }
 
 pub fn get_default_aws_path() -> PathBuf {
     let user_dirs = UserDirs::new().expect("Could not find user directories");
     let home_dir = user_dirs.home_dir();
-    let aws_config_dir = home_dir.join(".aws");
 
-    aws_config_dir
+    home_dir.join(".aws")
 }
 
-pub fn export_env_vars(credentials: &RoleCredentials, aws_config_path: ConfigOption) -> Result<(), anyhow::Error> {
-    let file_path =&PathBuf::from(&aws_config_path.value).join("credentials");
-    
-    let mut config = Ini::new();
-    if !file_path.exists() {
-        let _ = std::fs::create_dir_all(file_path.parent().unwrap());
-        let _ = std::fs::write(file_path, "".as_bytes());        
-    }
-
-    config.with_section(Some("default".to_string()))
-            .set("AWS_ACCESS_KEY_ID", &credentials.access_key_id)
-            .set("AWS_SECRET_ACCESS_KEY", &credentials.secret_access_key)
-            .set("AWS_SESSION_TOKEN", &credentials.session_token);
-
-    let _ = config.write_to_file(file_path.clone());
-        
-    Ok(())
-
+/// Writes `credentials` into `profile` of the shared credentials file under
+/// `aws_config_path`, keeping every other profile and a backup of the file.
+/// A profile assumer didn't write is only replaced with `overwrite`.
+pub fn export_env_vars(credentials: &RoleCredentials, account: &AccountInfo, aws_config_path: ConfigOption, profile: &str, overwrite: bool) -> Result<(), anyhow::Error> {
+    let file_path = PathBuf::from(&aws_config_path.value).join("credentials");
+    CredentialsFile::new(&file_path).write_profile(profile, &account.account_id, credentials, overwrite)
 }
 
-pub fn export_profiles(account: AccountInfo, role: &str, config_provider: &ConfigOptions) -> Result<(), anyhow::Error> {
-    let start_url = &config_provider.options.iter().find(|option| option.name == "start_url").unwrap().value.clone();
+/// Writes an SSO profile for `role` to `~/.aws/config`, named by
+/// `profile_name_template`. Profiles assumer wrote for the same role under
+/// an older name are dropped, and a name already used by another role or by
+/// a profile assumer didn't write is an error.
+pub fn export_profiles(account: AccountInfo, role: &str, alias: &str, region: &str, config_provider: &ConfigOptions) -> Result<String, anyhow::Error> {
     let file_path = &PathBuf::from(&config_provider.options.iter().find(|option| option.name == "aws_config_path").unwrap().value).join("config");
-    let region = &config_provider.options.iter().find(|option| option.name == "region").unwrap().value;
-    let profile_name = format!("profile 'assumer-{}/{}'", account.account_name, role);
-    let output = "json";
-    
-    let mut config = Ini::new();
-    if !file_path.exists() {
-        let _ = std::fs::create_dir_all(file_path.parent().unwrap());
-        let _ = std::fs::write(file_path, "".as_bytes());                        
-    } else {
-        config = Ini::load_from_file(file_path.clone()).unwrap();
+    let naming = ProfileNaming::from_config(config_provider, "profile_name_template", profiles::DEFAULT_PROFILE_TEMPLATE);
+    let profile_name = format!("profile {}", naming.name(&account, role, alias));
+
+    let mut config = IniFile::load(file_path)?;
+    let owned_by_role = |config: &IniFile, section: &str| {
+        profiles::is_managed(ProfileFile::Config, section, config.get(section, MANAGED_KEY))
+            && config.get(section, "sso_account_id") == Some(account.account_id.as_str())
+            && config.get(section, "sso_role_name") == Some(role)
+    };
+    if config.has_section(&profile_name) && !owned_by_role(&config, &profile_name) {
+        return Err(Error::msg(format!(
+            "[{}] is already used by another profile, add {{account_id}} or {{role}} to profile_name_template",
+            profile_name
+        )));
+    }
+    let previous_names: Vec<String> = config.sections().into_iter()
+        .filter(|section| *section != profile_name && owned_by_role(&config, section))
+        .map(String::from)
+        .collect();
+    for section in previous_names {
+        config.remove_section(&section);
     }
 
-
-    config.with_section(Some(&profile_name))
-            .set("sso_account_id", &account.account_id)
-            .set("sso_role_name", role)
-            .set("sso_start_url", start_url)
-            .set("sso_region", region)
-            .set("output", output)
-            .set("source_profile", "default");
-
-    let _ = config.write_to_file(file_path.clone());
+    if let Some((session, values)) = profiles::sso_session_section(config_provider) {
+        for (key, value) in values {
+            config.set(&session, key, &value);
+        }
+    }
+    profiles::write_sso_profile(&mut config, &profile_name, &account.account_id, role, region, config_provider);
+    config.write_to_file(file_path)?;
         
-    Ok(())
+    Ok(profile_name)
 }
 
 // fn check_for_granted_extension() -> bool {
@@ -1,2 +1,5 @@
 pub mod json;
-pub mod serde;
\ No newline at end of file
+pub mod serde;
+pub mod mask;
+pub mod diff;
+pub mod ini_file;
\ No newline at end of file
@@ -0,0 +1,91 @@
+/// A unified diff of two texts, line by line, with `context` unchanged
+/// lines around each change. Empty when the texts are the same.
+pub fn unified_diff(old: &str, new: &str, old_name: &str, new_name: &str, context: usize) -> String {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+    let edits = edits(&old_lines, &new_lines);
+    if edits.iter().all(|edit| matches!(edit, Edit::Same(..))) {
+        return String::new();
+    }
+
+    let mut output = format!("--- {}\n+++ {}\n", old_name, new_name);
+    let changed: Vec<usize> = edits.iter().enumerate()
+        .filter(|(_, edit)| !matches!(edit, Edit::Same(..)))
+        .map(|(i, _)| i)
+        .collect();
+
+    // Group changes whose context overlaps into hunks
+    let mut hunks: Vec<(usize, usize)> = vec![];
+    for &i in &changed {
+        let start = i.saturating_sub(context);
+        let end = (i + context + 1).min(edits.len());
+        match hunks.last_mut() {
+            Some((_, last_end)) if start <= *last_end => *last_end = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+
+    for (start, end) in hunks {
+        let (mut old_start, mut new_start) = (0, 0);
+        for edit in &edits[..start] {
+            match edit {
+                Edit::Same(..) => { old_start += 1; new_start += 1; }
+                Edit::Removed(_) => old_start += 1,
+                Edit::Added(_) => new_start += 1,
+            }
+        }
+        let old_count = edits[start..end].iter().filter(|edit| !matches!(edit, Edit::Added(_))).count();
+        let new_count = edits[start..end].iter().filter(|edit| !matches!(edit, Edit::Removed(_))).count();
+        output.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            old_start + usize::from(old_count > 0), old_count,
+            new_start + usize::from(new_count > 0), new_count,
+        ));
+        for edit in &edits[start..end] {
+            match edit {
+                Edit::Same(line) => output.push_str(&format!(" {}\n", line)),
+                Edit::Removed(line) => output.push_str(&format!("-{}\n", line)),
+                Edit::Added(line) => output.push_str(&format!("+{}\n", line)),
+            }
+        }
+    }
+    output
+}
+
+enum Edit<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// Shortest edit script from a longest common subsequence table. Config
+/// files are small, so the quadratic table is fine.
+fn edits<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<Edit<'a>> {
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut edits = vec![];
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            edits.push(Edit::Same(old[i]));
+            i += 1;
+            j += 1;
+        } else if j < new.len() && (i == old.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            edits.push(Edit::Added(new[j]));
+            j += 1;
+        } else {
+            edits.push(Edit::Removed(old[i]));
+            i += 1;
+        }
+    }
+    edits
+}
@@ -0,0 +1,302 @@
+use anyhow::Result;
+use std::{fmt, fs::{self, OpenOptions}, io::Write, path::Path};
+
+/// An INI file edited in place: comments, blank lines, key order and the
+/// spacing around `=` are kept exactly as written, and only the keys and
+/// sections that are changed get touched.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IniFile {
+    lines: Vec<String>,
+    newline: &'static str,
+    /// Whether the text ended with a newline. Kept so that an unchanged file
+    /// writes back byte for byte.
+    trailing_newline: bool,
+}
+
+impl IniFile {
+    pub fn parse(contents: &str) -> Self {
+        Self {
+            lines: contents.lines().map(String::from).collect(),
+            newline: if contents.contains("\r\n") { "\r\n" } else { "\n" },
+            trailing_newline: contents.is_empty() || contents.ends_with('\n'),
+        }
+    }
+
+    /// Reads `path`, or starts an empty file if it doesn't exist yet.
+    pub fn load(path: &Path) -> Result<Self> {
+        if path.exists() {
+            Ok(Self::parse(&fs::read_to_string(path)?))
+        } else {
+            Ok(Self::parse(""))
+        }
+    }
+
+    /// Every section name, in file order.
+    pub fn sections(&self) -> Vec<&str> {
+        self.lines.iter().filter_map(|line| section_name(line)).collect()
+    }
+
+    pub fn has_section(&self, section: &str) -> bool {
+        self.section_range(section).is_some()
+    }
+
+    /// The `key = value` pairs of `section`, in file order.
+    pub fn entries(&self, section: &str) -> Vec<(&str, &str)> {
+        let Some((start, end)) = self.section_range(section) else { return vec![] };
+        self.lines[start + 1..end].iter().filter_map(|line| key_value(line)).collect()
+    }
+
+    /// The value of `key` in `section`. When a key is repeated the last one
+    /// wins.
+    pub fn get(&self, section: &str, key: &str) -> Option<&str> {
+        self.entries(section).into_iter().rev().find(|(name, _)| *name == key).map(|(_, value)| value)
+    }
+
+    /// Sets `key` in `section`, replacing the value where it is or adding it
+    /// after the section's last setting. A missing section is added at the
+    /// end of the file.
+    pub fn set(&mut self, section: &str, key: &str, value: &str) {
+        let Some((start, end)) = self.section_range(section) else {
+            while self.lines.last().is_some_and(|line| line.trim().is_empty()) {
+                self.lines.pop();
+            }
+            if !self.lines.is_empty() {
+                self.lines.push(String::new());
+            }
+            self.lines.push(format!("[{}]", section));
+            self.lines.push(format!("{} = {}", key, value));
+            return;
+        };
+
+        let existing = (start + 1..end).rev().find(|&i| key_value(&self.lines[i]).is_some_and(|(name, _)| name == key));
+        if let Some(i) = existing {
+            let line = &self.lines[i];
+            let (before, after) = line.split_at(line.find('=').unwrap() + 1);
+            let spacing = &after[..after.len() - after.trim_start().len()];
+            self.lines[i] = format!("{}{}{}", before, spacing, value);
+            return;
+        }
+
+        // Follow the spacing of the section's other settings
+        let separator = self.lines[start + 1..end].iter()
+            .rev()
+            .find(|line| key_value(line).is_some())
+            .map_or(" = ", |line| if line.contains(" = ") { " = " } else { "=" });
+        let insert_at = (start + 1..end).rev()
+            .find(|&i| !self.lines[i].trim().is_empty())
+            .map_or(start + 1, |i| i + 1);
+        self.lines.insert(insert_at, format!("{}{}{}", key, separator, value));
+    }
+
+    /// Removes every occurrence of `key` from `section`.
+    pub fn remove_key(&mut self, section: &str, key: &str) {
+        let Some((start, end)) = self.section_range(section) else { return };
+        let mut i = start + 1;
+        let mut end = end;
+        while i < end {
+            if key_value(&self.lines[i]).is_some_and(|(name, _)| name == key) {
+                self.lines.remove(i);
+                end -= 1;
+            } else {
+                i += 1;
+            }
+        }
+    }
+
+    /// Removes `section`, from its header up to the next one.
+    pub fn remove_section(&mut self, section: &str) {
+        let Some((start, end)) = self.section_range(section) else { return };
+        let was_last = end == self.lines.len();
+        self.lines.drain(start..end);
+        if was_last {
+            while self.lines.last().is_some_and(|line| line.trim().is_empty()) {
+                self.lines.pop();
+            }
+        }
+    }
+
+    pub fn rename_section(&mut self, from: &str, to: &str) {
+        if let Some((start, _)) = self.section_range(from) {
+            self.lines[start] = format!("[{}]", to);
+        }
+    }
+
+    /// Writes the file through a temporary file, so a failed write never
+    /// leaves it half done. An existing file keeps its permissions, and on
+    /// Unix a new one is only readable by the current user.
+    pub fn write_to_file(&self, path: &Path) -> Result<()> {
+        let permissions = fs::metadata(path).ok().map(|metadata| metadata.permissions());
+        self.write(path, permissions)
+    }
+
+    /// Like [IniFile::write_to_file], but on Unix the file is only readable
+    /// by the current user.
+    pub fn write_to_private_file(&self, path: &Path) -> Result<()> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            self.write(path, Some(fs::Permissions::from_mode(0o600)))
+        }
+        #[cfg(not(unix))]
+        self.write_to_file(path)
+    }
+
+    fn write(&self, path: &Path, permissions: Option<fs::Permissions>) -> Result<()> {
+        // Replace the file a symlink points to rather than the symlink, so
+        // dotfiles managed elsewhere stay linked
+        let path = &fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // The temporary file starts out private, so the credentials file's
+        // contents are never readable by others, even briefly. One left
+        // behind by an earlier failed write may not be, so it goes first.
+        let temp_path = path.with_extension("assumer-tmp");
+        if temp_path.exists() {
+            fs::remove_file(&temp_path)?;
+        }
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options.open(&temp_path)?;
+        file.write_all(self.to_string().as_bytes())?;
+        drop(file);
+        if let Some(permissions) = permissions {
+            fs::set_permissions(&temp_path, permissions)?;
+        }
+        fs::rename(&temp_path, path)?;
+        Ok(())
+    }
+
+    /// The header line of `section` and the end of its body.
+    fn section_range(&self, section: &str) -> Option<(usize, usize)> {
+        let start = self.lines.iter().position(|line| section_name(line) == Some(section))?;
+        let end = self.lines[start + 1..].iter()
+            .position(|line| section_name(line).is_some())
+            .map_or(self.lines.len(), |offset| start + 1 + offset);
+        Some((start, end))
+    }
+}
+
+impl fmt::Display for IniFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.lines.is_empty() {
+            return Ok(());
+        }
+        f.write_str(&self.lines.join(self.newline))?;
+        if self.trailing_newline {
+            f.write_str(self.newline)?;
+        }
+        Ok(())
+    }
+}
+
+/// The byte order mark some Windows editors start files with.
+const BOM: char = '\u{feff}';
+
+fn section_name(line: &str) -> Option<&str> {
+    line.trim_start_matches(BOM).trim().strip_prefix('[')?.strip_suffix(']').map(str::trim)
+}
+
+fn key_value(line: &str) -> Option<(&str, &str)> {
+    let trimmed = line.trim_start_matches(BOM).trim_start();
+    if trimmed.starts_with('#') || trimmed.starts_with(';') || trimmed.starts_with('[') {
+        return None;
+    }
+    let (key, value) = trimmed.split_once('=')?;
+    Some((key.trim(), value.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IniFile;
+    use std::fs;
+
+    fn round_trip(contents: &str) {
+        assert_eq!(IniFile::parse(contents).to_string(), contents);
+    }
+
+    #[test]
+    fn keeps_unchanged_files_byte_for_byte() {
+        round_trip("");
+        round_trip("# comment\n; another\n\n[default]\nregion=eu-west-1\noutput = json\n");
+        round_trip("[default]\r\nregion = eu-west-1\r\n\r\n[profile dev]\r\nregion = us-east-1\r\n");
+        round_trip("[default]\nregion = eu-west-1");
+        round_trip("\u{feff}[default]\nregion = eu-west-1\n");
+    }
+
+    #[test]
+    fn keeps_comments_order_and_spacing_when_setting_keys() {
+        let mut file = IniFile::parse("# work\n[default]\n# the main region\nregion=eu-west-1\noutput=json\n\n[profile dev]\nregion = us-east-1\n");
+        file.set("default", "region", "eu-central-1");
+        file.set("default", "cli_pager", "");
+        file.set("profile dev", "output", "text");
+        assert_eq!(
+            file.to_string(),
+            "# work\n[default]\n# the main region\nregion=eu-central-1\noutput=json\ncli_pager=\n\n[profile dev]\nregion = us-east-1\noutput = text\n"
+        );
+    }
+
+    #[test]
+    fn keeps_line_endings_and_missing_trailing_newlines() {
+        let mut file = IniFile::parse("[default]\r\nregion = eu-west-1");
+        file.set("default", "output", "json");
+        assert_eq!(file.to_string(), "[default]\r\nregion = eu-west-1\r\noutput = json");
+    }
+
+    #[test]
+    fn reads_the_last_of_repeated_keys_and_sets_it() {
+        let mut file = IniFile::parse("[default]\nregion = eu-west-1\nregion = us-east-1\n");
+        assert_eq!(file.get("default", "region"), Some("us-east-1"));
+        file.set("default", "region", "ap-south-1");
+        assert_eq!(file.to_string(), "[default]\nregion = eu-west-1\nregion = ap-south-1\n");
+        file.remove_key("default", "region");
+        assert_eq!(file.to_string(), "[default]\n");
+    }
+
+    #[test]
+    fn adds_missing_sections_and_ignores_removals_from_them() {
+        let mut file = IniFile::parse("[default]\nregion = eu-west-1\n\n");
+        file.remove_key("profile dev", "region");
+        file.remove_section("profile dev");
+        assert_eq!(file.to_string(), "[default]\nregion = eu-west-1\n\n");
+        file.set("profile dev", "region", "us-east-1");
+        assert_eq!(file.to_string(), "[default]\nregion = eu-west-1\n\n[profile dev]\nregion = us-east-1\n");
+    }
+
+    #[test]
+    fn finds_sections_after_a_byte_order_mark() {
+        let mut file = IniFile::parse("\u{feff}[default]\nregion = eu-west-1\n");
+        assert_eq!(file.sections(), vec!["default"]);
+        assert_eq!(file.get("default", "region"), Some("eu-west-1"));
+        file.set("default", "region", "us-east-1");
+        assert_eq!(file.to_string(), "\u{feff}[default]\nregion = us-east-1\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn writes_through_symlinks_and_replaces_stale_temp_files() {
+        use std::os::unix::fs::{symlink, PermissionsExt};
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("dotfiles-config");
+        let link = dir.path().join("config");
+        fs::write(&target, "[default]\n").unwrap();
+        symlink(&target, &link).unwrap();
+        let new_file = dir.path().join("new");
+        let stale = dir.path().join("new.assumer-tmp");
+        fs::write(&stale, "left over").unwrap();
+        fs::set_permissions(&stale, fs::Permissions::from_mode(0o644)).unwrap();
+
+        IniFile::parse("[default]\nregion = eu-west-1\n").write_to_file(&link).unwrap();
+        IniFile::parse("[default]\n").write_to_file(&new_file).unwrap();
+
+        assert!(fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "[default]\nregion = eu-west-1\n");
+        assert_eq!(fs::read_to_string(&new_file).unwrap(), "[default]\n");
+        assert_eq!(fs::metadata(&new_file).unwrap().permissions().mode() & 0o777, 0o600);
+    }
+}
@@ -1,6 +1,6 @@
 use anyhow::{anyhow, Result};
 use serde::{de::DeserializeOwned, Serialize};
-use std::fs::File;
+use std::fs::{File, OpenOptions};
 use std::io::{BufReader, Write};
 use std::path::Path;
 
@@ -11,7 +11,31 @@ where
     T: ?Sized + Serialize,
 {
     let serialized = serde_json::to_string(d)?;
-    let mut file = File::create(&file_path)?;
+    let mut file = File::create(file_path)?;
+    file.write_all(serialized.as_bytes())?;
+    Ok(())
+}
+
+/// Like [write_to_file], but on Unix the file is only readable by the
+/// current user. Used for anything holding tokens or credentials.
+pub fn write_to_private_file<T>(file_path: &Path, d: &T) -> Result<()>
+where
+    T: ?Sized + Serialize,
+{
+    let serialized = serde_json::to_string(d)?;
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(file_path)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+    }
     file.write_all(serialized.as_bytes())?;
     Ok(())
 }
@@ -0,0 +1,52 @@
+/// Hides the middle of `value`, keeping `visible` characters at each end so
+/// it can still be told apart from others. Short values are hidden entirely.
+pub fn mask_secret(value: &str, visible: usize) -> String {
+    let chars: Vec<char> = value.chars().collect();
+    if chars.len() <= visible * 4 {
+        return "*".repeat(chars.len());
+    }
+    let start: String = chars[..visible].iter().collect();
+    let end: String = chars[chars.len() - visible..].iter().collect();
+    format!("{}{}{}", start, "*".repeat(12), end)
+}
+
+/// Hides all but the last four digits of an account ID.
+pub fn mask_account_id(account_id: &str) -> String {
+    let chars: Vec<char> = account_id.chars().collect();
+    let hidden = chars.len().saturating_sub(4);
+    "*".repeat(hidden) + &chars[hidden..].iter().collect::<String>()
+}
+
+/// `text` with every run of exactly twelve digits, as account IDs are,
+/// masked by [mask_account_id].
+pub fn mask_account_ids(text: &str) -> String {
+    let mut masked = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(|c: char| c.is_ascii_digit()) {
+        masked.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+        let digits = &rest[..end];
+        if digits.len() == 12 {
+            masked.push_str(&mask_account_id(digits));
+        } else {
+            masked.push_str(digits);
+        }
+        rest = &rest[end..];
+    }
+    masked.push_str(rest);
+    masked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::mask_account_ids;
+
+    #[test]
+    fn masks_only_twelve_digit_runs() {
+        assert_eq!(
+            mask_account_ids("[profile 111111111111/Admin]\nsso_account_id = 222222222222\nduration = 3600 1234567890123\n"),
+            "[profile ********1111/Admin]\nsso_account_id = ********2222\nduration = 3600 1234567890123\n"
+        );
+    }
+}
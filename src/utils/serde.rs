@@ -17,7 +17,7 @@ pub mod json_date_format {
     use chrono::{DateTime, Utc, TimeZone};
     use serde::{self, Deserialize, Serializer, Deserializer};
 
-    const FORMAT: &'static str = "%Y-%m-%dT%H:%M:%SZ";
+    const FORMAT: &str = "%Y-%m-%dT%H:%M:%SZ";
 
     pub fn serialize<S>(
         date: &DateTime<Utc>,
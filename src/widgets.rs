@@ -2,8 +2,14 @@ pub mod accounts;
 pub mod credentials;
 pub mod roles;
 pub mod config;
+pub mod metadata;
+pub mod prune;
+pub mod review;
 
 pub use accounts::*;
 pub use credentials::*;
 pub use roles::*;
-pub use config::*;
\ No newline at end of file
+pub use config::*;
+pub use metadata::*;
+pub use prune::*;
+pub use review::*;
\ No newline at end of file
@@ -6,6 +6,8 @@ use ratatui::{
     }, Frame
 };
 
+use chrono::Local;
+
 use crate::app::App;
 
 pub fn get_layout(f: &mut Frame) -> Rc<[Rect]> {
@@ -13,6 +15,13 @@ pub fn get_layout(f: &mut Frame) -> Rc<[Rect]> {
 }
 
 pub fn handle_key_events(app: &mut App, key: KeyEvent) -> Result<(), ()>{
+    if app.searching {
+        return handle_search_key_events(app, key);
+    }
+    if app.entering_arn {
+        return handle_arn_key_events(app, key);
+    }
+
     match key.code {
         KeyCode::Down => {
             app.next();
@@ -24,6 +33,41 @@ pub fn handle_key_events(app: &mut App, key: KeyEvent) -> Result<(), ()>{
             app.select_account();
             app.current_page = crate::app::CurrentPage::Roles;
         }
+        KeyCode::Char('/') => {
+            app.searching = true;
+        }
+        KeyCode::Char('a') => {
+            app.arn_input.clear();
+            app.entering_arn = true;
+        }
+        KeyCode::Char('r') if app.inventory_sync.is_none() => {
+            app.get_account_list();
+        }
+        KeyCode::Char('e') => {
+            app.show_email = !app.show_email;
+        }
+        KeyCode::Char('p') => {
+            app.presentation_mode = !app.presentation_mode;
+        }
+        KeyCode::Char('m') => {
+            app.edit_metadata();
+            if app.editing_metadata {
+                app.current_page = crate::app::CurrentPage::Metadata;
+            }
+        }
+        KeyCode::Char('s') => {
+            app.sync_profiles();
+        }
+        KeyCode::Char('x') => {
+            app.open_prune();
+            if app.pruning {
+                app.current_page = crate::app::CurrentPage::Prune;
+            }
+        }
+        KeyCode::Esc if !app.search_query.is_empty() => {
+            app.search_query.clear();
+            app.apply_filter();
+        }
         KeyCode::Char('c') => {
             app.currently_editing = true;
             app.current_page = crate::app::CurrentPage::Config;
@@ -37,6 +81,57 @@ pub fn handle_key_events(app: &mut App, key: KeyEvent) -> Result<(), ()>{
     Ok(())
 }
 
+fn handle_search_key_events(app: &mut App, key: KeyEvent) -> Result<(), ()> {
+    match key.code {
+        KeyCode::Down => {
+            app.next();
+        }
+        KeyCode::Up => {
+            app.previous();
+        }
+        KeyCode::Enter => {
+            app.searching = false;
+        }
+        KeyCode::Esc => {
+            app.searching = false;
+            app.search_query.clear();
+            app.apply_filter();
+        }
+        KeyCode::Char(value) => {
+            app.search_query.push(value);
+            app.apply_filter();
+        }
+        KeyCode::Backspace => {
+            app.search_query.pop();
+            app.apply_filter();
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+fn handle_arn_key_events(app: &mut App, key: KeyEvent) -> Result<(), ()> {
+    match key.code {
+        KeyCode::Enter => {
+            app.entering_arn = false;
+            app.open_arn();
+        }
+        KeyCode::Esc => {
+            app.entering_arn = false;
+        }
+        KeyCode::Char(value) => {
+            app.arn_input.push(value);
+        }
+        KeyCode::Backspace => {
+            app.arn_input.pop();
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
 pub fn render_accounts(f: &mut Frame, app: &mut App, area: Rect) {
     let style = {
         if app.is_selected {
@@ -52,6 +147,22 @@ pub fn render_accounts(f: &mut Frame, app: &mut App, area: Rect) {
         "<Down>".blue().bold(),
         " Select Account ".into(),
         "<Right>".blue().bold(),
+        " Search ".into(),
+        "</>".blue().bold(),
+        " Open ARN ".into(),
+        "<A>".blue().bold(),
+        " Refresh ".into(),
+        "<R>".blue().bold(),
+        " Emails ".into(),
+        "<E>".blue().bold(),
+        " Details ".into(),
+        "<M>".blue().bold(),
+        " Presentation ".into(),
+        "<P>".blue().bold(),
+        " Sync Profiles ".into(),
+        "<S>".blue().bold(),
+        " Prune ".into(),
+        "<X>".blue().bold(),
         " Config ".into(),
         "<C>".yellow().bold(),
         " Quit ".into(),
@@ -61,8 +172,17 @@ pub fn render_accounts(f: &mut Frame, app: &mut App, area: Rect) {
     let start_url = app.config_options.options.iter().find(|option| option.name == "start_url").unwrap().value.clone();
     let url_title = Title::from(format!(" Start URL: {} ", start_url).bold());
 
-    let account_list_title = Title::from(format!(" Accounts ({}) ", app.rows.len()).bold());        
-    let account_list_block = Block::bordered()
+    let account_count = if app.search_query.is_empty() {
+        format!("{}", app.rows.len())
+    } else {
+        format!("{}/{}", app.visible_rows.len(), app.rows.len())
+    };
+    let mut account_list_title = Line::from(format!(" Accounts ({}) ", account_count).bold());
+    if app.presentation_mode {
+        account_list_title.push_span("[Presentation mode] ".yellow().bold());
+    }
+    let account_list_title = Title::from(account_list_title);        
+    let mut account_list_block = Block::bordered()
         .title(account_list_title.alignment(Alignment::Left))   
         .title(instructions
             .alignment(Alignment::Center)
@@ -71,28 +191,109 @@ pub fn render_accounts(f: &mut Frame, app: &mut App, area: Rect) {
         .title (url_title.alignment(Alignment::Right))     
         .border_set(border::THICK);
 
-    let widths = [
+    if app.searching || !app.search_query.is_empty() {
+        let cursor = if app.searching { "_" } else { "" };
+        let search_title = Title::from(Line::from(vec![
+            " Search: ".into(),
+            format!("{}{} ", app.search_query, cursor).yellow().bold(),
+        ]));
+        account_list_block = account_list_block.title(search_title
+            .alignment(Alignment::Left)
+            .position(Position::Bottom)
+        );
+    }
+
+    if app.entering_arn {
+        let arn_title = Title::from(Line::from(vec![
+            " ARN: ".into(),
+            format!("{}_ ", app.arn_input).yellow().bold(),
+        ]));
+        account_list_block = account_list_block.title(arn_title
+            .alignment(Alignment::Left)
+            .position(Position::Bottom)
+        );
+    }
+
+    let throttled = app.rows.iter().filter(|row| row.throttled).count();
+    let sync_status = match (app.role_prefetch_progress(), &app.sync_error, app.last_synced) {
+        (Some((done, total)), _, _) if throttled > 0 => format!(" Syncing roles {}/{}, {} throttled ", done, total, throttled).yellow(),
+        (Some((done, total)), _, _) => format!(" Syncing roles {}/{} ", done, total).yellow(),
+        (None, Some(err), _) if app.sync_throttled => format!(" {} ", err).yellow(),
+        (None, Some(err), _) => format!(" Sync failed: {} ", err).red(),
+        (None, None, _) if throttled > 0 => format!(" {} accounts throttled, refresh with <R> ", throttled).yellow(),
+        (None, None, Some(synced_at)) if app.inventory_stale => format!(" Last synced: {} (stale) ", synced_at.with_timezone(&Local).format("%Y-%m-%d %H:%M")).yellow(),
+        (None, None, Some(synced_at)) => format!(" Last synced: {} ", synced_at.with_timezone(&Local).format("%Y-%m-%d %H:%M")).into(),
+        (None, None, None) => "".into(),
+    };
+    account_list_block = account_list_block.title(Title::from(sync_status)
+        .alignment(Alignment::Right)
+        .position(Position::Bottom)
+    );
+
+    let show_tags = app.visible_rows.iter()
+        .any(|&i| app.metadata.get(&app.rows[i].account_id).is_some_and(|metadata| !metadata.tags.is_empty()));
+    let mut widths = vec![
         Constraint::Min(10),
+        Constraint::Min(20),
         Constraint::Min(20)
     ];
+    let mut header = vec!["Account Name", "Account ID", "Roles"];
+    if show_tags {
+        widths.insert(2, Constraint::Min(10));
+        header.insert(2, "Tags");
+    }
+    if app.show_email {
+        widths.insert(2, Constraint::Min(20));
+        header.insert(2, "Email");
+    }
 
-    let rows = app.rows.iter().map(|row| {
-        Row::new(vec![
-            Cell::from(row.account_name.clone()),
-            Cell::from(row.account_id.clone())
-        ])
+    let rows = app.visible_rows.iter().map(|&i| {
+        let row = &app.rows[i];
+        let metadata = app.metadata.get(&row.account_id).cloned().unwrap_or_default();
+        let name = if metadata.alias.is_empty() {
+            Line::from(row.account_name.clone())
+        } else {
+            Line::from(vec![
+                metadata.alias.clone().bold(),
+                format!(" ({})", row.account_name).into(),
+            ])
+        };
+        let mut cells = vec![
+            Cell::from(name),
+            Cell::from(app.display_account_id(&row.account_id)),
+            if row.throttled && row.roles.is_empty() {
+                Cell::from("throttled").style(Style::new().yellow())
+            } else {
+                Cell::from(row.roles.join(", "))
+            }
+        ];
+        if show_tags {
+            cells.insert(2, Cell::from(metadata.tags.join(", ")));
+        }
+        if app.show_email {
+            cells.insert(2, Cell::from(row.email.clone()));
+        }
+        Row::new(cells)
     });    
 
+    let note = app.metadata.get(&app.selected_account.account_id)
+        .map(|metadata| metadata.note.clone())
+        .unwrap_or_default();
     let footer_row = Row::new(vec![
         Cell::from("Selected Account:").style(Style::new().bold()),
-        Cell::from(app.selected_account.account_id.clone()).style(Style::new().bold().yellow())
+        Cell::from(app.display_account_id(&app.selected_account.account_id)).style(Style::new().bold().yellow()),
+        if app.credential_message.is_empty() {
+            Cell::from(note).style(Style::new().italic())
+        } else {
+            Cell::from(app.credential_message.clone()).style(Style::new().bold().yellow())
+        }
     ]);    
 
     let table = Table::new(rows, widths)
         .column_spacing(1)
         .style(style)
         .header(
-            Row::new(vec!["Account Name", "Account ID"])
+            Row::new(header)
                 .style(Style::new().bold())                            
         )                                
         .footer(footer_row)
@@ -18,13 +18,14 @@ pub fn handle_key_events(app: &mut App, key: KeyEvent) -> Result<(), anyhow::Err
             app.currently_editing = false;
             let mut config = app.load_config().unwrap();
             app.config_options.options.iter().for_each(|option| {
-                config.with_section(Some("Main".to_string()))
-                    .set(option.name.clone(), option.value.clone());                
+                config.set("Main", &option.name, &option.value);
             });
             app.update_config(&mut config).map_err(|err| {
                 anyhow::anyhow!("Failed to update config: {}", err)
             })?;            
 
+            app.load_cached_inventory();
+            app.reset_credential_cache();
             app.load_aws_config(Some(true));
             app.get_account_list();
             app.current_page = crate::app::CurrentPage::AccountList;
@@ -46,7 +47,7 @@ pub fn handle_key_events(app: &mut App, key: KeyEvent) -> Result<(), anyhow::Err
         KeyCode::Up => {
             let i = match app.config_table_state.selected() {
                 Some(i) => {
-                    if i <= 0 {
+                    if i == 0 {
                         1
                     } else {
                         i - 1
@@ -57,18 +58,14 @@ pub fn handle_key_events(app: &mut App, key: KeyEvent) -> Result<(), anyhow::Err
             app.config_table_state.select(Some(i));
             app.value_input = app.config_options.options[i].value.clone();
         },
-        KeyCode::Char(value) => {                      
-            if app.config_table_state.selected() != None {
-                let i = app.config_table_state.selected().unwrap();
-                app.config_options.options[i].value.push(value);
-            }
+        KeyCode::Char(value) if app.config_table_state.selected().is_some() => {
+            let i = app.config_table_state.selected().unwrap();
+            app.config_options.options[i].value.push(value);
+        },
+        KeyCode::Backspace if app.config_table_state.selected().is_some() => {
+            let i = app.config_table_state.selected().unwrap();
+            app.config_options.options[i].value.pop();
         },
-        KeyCode::Backspace => {
-            if app.config_table_state.selected() != None {
-                let i = app.config_table_state.selected().unwrap();
-                app.config_options.options[i].value.pop();
-            }
-        },      
         KeyCode::Esc => {
             app.currently_editing = false;
             app.exit();
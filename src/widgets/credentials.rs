@@ -2,25 +2,63 @@ use std::rc::Rc;
 
 use ratatui::{    
     crossterm::event::{KeyCode, KeyEvent}, layout::{Alignment, Constraint, Layout, Rect}, style::{Style, Stylize}, symbols::border, text::Line, widgets::{
-        block::{Position, Title}, Block, Cell, Row, Table
+        block::{Position, Title}, Block, Cell, Clear, Row, Table
     }, Frame
 };
 
-use crate::app::App;
+use chrono::{Duration, Local};
+
+use crate::{app::App, console, exporters};
 
 pub fn get_layout(f: &mut Frame) -> Rc<[Rect]> {
     Layout::horizontal([Constraint::Min(5)]).split(f.size())
 }
 
 pub fn handle_key_events(app: &mut App, key: KeyEvent) -> Result<(), ()>{
+    if app.entering_mfa_code {
+        return handle_mfa_code_key_events(app, key);
+    }
+    if app.format_picker_open {
+        return handle_format_picker_key_events(app, key);
+    }
+    if app.console_picker_open {
+        return handle_console_picker_key_events(app, key);
+    }
+    if !matches!(key.code, KeyCode::Char(c) if app.pending_confirmation == Some(c)) {
+        app.pending_confirmation = None;
+    }
     match key.code {
         KeyCode::Char('q') => app.exit(),                
         KeyCode::Char('c') => {            
             app.credential_message = "Opening AWS Console...".to_string();
             app.open_console()
         }       
+        KeyCode::Char('o') => {
+            app.console_table_state.select(Some(0));
+            app.console_picker_open = true;
+        },
         KeyCode::Char('e') => {            
-            let _ = app.export();
+            app.export();
+        },
+        KeyCode::Char('y') => {
+            app.copy_credentials();
+        },
+        KeyCode::Char('u') => {
+            app.copy_console_url();
+        },
+        KeyCode::Char('w') => {
+            app.save_credentials();
+        },
+        KeyCode::Char('r') => {
+            app.toggle_secrets();
+        },
+        KeyCode::Char('p') => {
+            app.presentation_mode = !app.presentation_mode;
+        },
+        KeyCode::Char('f') => {
+            let selected = exporters::all().iter().position(|exporter| exporter.name() == app.exporter().name());
+            app.format_table_state.select(selected);
+            app.format_picker_open = true;
         },
         KeyCode::Left => {            
             app.role_is_selected = false;     
@@ -32,18 +70,105 @@ pub fn handle_key_events(app: &mut App, key: KeyEvent) -> Result<(), ()>{
     Ok(())
 }
 
+fn handle_mfa_code_key_events(app: &mut App, key: KeyEvent) -> Result<(), ()> {
+    match key.code {
+        KeyCode::Enter => {
+            app.select_role();
+        }
+        KeyCode::Esc => {
+            app.entering_mfa_code = false;
+            app.role_is_selected = false;
+            app.current_page = crate::app::CurrentPage::Roles;
+        }
+        KeyCode::Char(value) => {
+            app.mfa_code_input.push(value);
+        }
+        KeyCode::Backspace => {
+            app.mfa_code_input.pop();
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+fn handle_format_picker_key_events(app: &mut App, key: KeyEvent) -> Result<(), ()> {
+    let count = exporters::all().len();
+    match key.code {
+        KeyCode::Down => {
+            let i = app.format_table_state.selected().map_or(0, |i| (i + 1) % count);
+            app.format_table_state.select(Some(i));
+        }
+        KeyCode::Up => {
+            let i = app.format_table_state.selected().map_or(0, |i| (i + count - 1) % count);
+            app.format_table_state.select(Some(i));
+        }
+        KeyCode::Enter => {
+            if let Some(exporter) = app.format_table_state.selected().and_then(|i| exporters::all().into_iter().nth(i)) {
+                app.export_format = exporter.name().to_string();
+            }
+            app.format_picker_open = false;
+        }
+        KeyCode::Esc => {
+            app.format_picker_open = false;
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+fn handle_console_picker_key_events(app: &mut App, key: KeyEvent) -> Result<(), ()> {
+    let count = console::SERVICES.len();
+    match key.code {
+        KeyCode::Down => {
+            let i = app.console_table_state.selected().map_or(0, |i| (i + 1) % count);
+            app.console_table_state.select(Some(i));
+        }
+        KeyCode::Up => {
+            let i = app.console_table_state.selected().map_or(0, |i| (i + count - 1) % count);
+            app.console_table_state.select(Some(i));
+        }
+        KeyCode::Enter => {
+            app.console_picker_open = false;
+            if let Some(service) = app.console_table_state.selected().and_then(|i| console::SERVICES.get(i)) {
+                app.credential_message = format!("Opening {}...", service.name);
+                app.open_console_service(service);
+            }
+        }
+        KeyCode::Esc => {
+            app.console_picker_open = false;
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
 pub fn render_credentials(f: &mut Frame, app: &mut App, area: Rect) {
     let instructions = Title::from(Line::from(vec![
         " Back ".into(),
         "<Left>".blue().bold(),        
         " Console ".into(),
         "<C>".blue().bold(),
+        " Open at ".into(),
+        "<O>".blue().bold(),
+        " Copy URL ".into(),
+        "<U>".blue().bold(),
         " Export ".into(),
         "<E>".blue().bold(),
+        " Copy ".into(),
+        "<Y>".blue().bold(),
+        " Write ".into(),
+        "<W>".blue().bold(),
+        " Format ".into(),
+        format!("<F> {}", app.exporter().name()).blue().bold(),
+        if app.secrets_revealed() { " Hide ".into() } else { " Reveal ".into() },
+        "<R>".blue().bold(),
         " Quit ".into(),
         "<Q> ".blue().bold(),
     ]));
-    let title = Title::from(format!("Credentials for {} - {}", app.selected_account.account_name, app.selected_role).bold());        
+    let title = Title::from(format!("Credentials for {} - {}", app.selected_account.account_name, app.role_credentials.name).bold());        
     let block = Block::bordered()
         .title(title.alignment(Alignment::Left))   
         .title(instructions
@@ -57,33 +182,138 @@ pub fn render_credentials(f: &mut Frame, app: &mut App, area: Rect) {
         Constraint::Min(10),
     ];
 
-    let rows = vec![
+    let mut rows = vec![
         Row::new(vec![
             Cell::from("Access Key ID"),
             Cell::from(app.role_credentials.access_key_id.clone())
         ]),
         Row::new(vec![
             Cell::from("Secret Access Key"),
-            Cell::from(app.role_credentials.secret_access_key.clone())
+            Cell::from(app.display_secret(&app.role_credentials.secret_access_key))
         ]),
         Row::new(vec![
             Cell::from("Session Token"),
-            Cell::from(app.role_credentials.session_token.clone())
-        ]),
-        Row::new(vec![
-            Cell::from("Expiration"),
-            Cell::from(app.role_credentials.expiration.clone())
+            Cell::from(app.display_secret(&app.role_credentials.session_token))
         ]),
     ];
+    if app.credentials_error.is_none() && !app.entering_mfa_code {
+        let expiration = app.role_credentials.expiration;
+        let remaining = app.role_credentials.expires_in();
+        let countdown_style = if remaining < Duration::minutes(5) {
+            Style::new().red().bold()
+        } else if app.role_credentials.expires_within(app.expiry_warning()) {
+            Style::new().yellow().bold()
+        } else {
+            Style::new().green()
+        };
+        rows.extend([
+            Row::new(vec![
+                Cell::from("Expiration"),
+                Cell::from(expiration.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S %Z").to_string())
+            ]),
+            Row::new(vec![
+                Cell::from("Expiration (UTC)"),
+                Cell::from(expiration.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+            ]),
+            Row::new(vec![
+                Cell::from(""),
+                Cell::from(app.role_credentials.expiry_countdown()).style(countdown_style)
+            ]),
+        ]);
+    }
+
+    let footer = match &app.credentials_error {
+        _ if app.entering_mfa_code => Row::new(vec!["MFA code".to_string(), format!("{}_", app.mfa_code_input)]).bold().yellow(),
+        Some(err) => Row::new(vec!["Error".to_string(), err.clone()]).bold().red(),
+        None => Row::new(vec!["".to_string(), app.credential_message.clone()]).bold().yellow(),
+    };
 
     // let mut binding = app.selected_account.clone();
     let table = Table::new(rows, widths)
         .column_spacing(1)
         .style(Style::new().blue())                              
-        .footer(Row::new(vec!["".to_string(), app.credential_message.clone()]).bold().yellow())
+        .footer(footer)
         .block(block)
         .highlight_style(Style::new().reversed())
         .highlight_symbol(">>");
 
     f.render_widget(table, area);
+
+    if app.format_picker_open {
+        render_format_picker(f, app, area);
+    }
+    if app.console_picker_open {
+        render_console_picker(f, app, area);
+    }
+}
+
+fn render_format_picker(f: &mut Frame, app: &mut App, area: Rect) {
+    let exporters = exporters::all();
+    let popup = centered(area, 60, exporters.len() as u16 + 4);
+    let block = Block::bordered()
+        .title(Title::from(" Export format ".bold()).alignment(Alignment::Center))
+        .title(Title::from(Line::from(vec![
+                " Choose ".into(),
+                "<Enter>".blue().bold(),
+                " Cancel ".into(),
+                "<Esc> ".blue().bold(),
+            ]))
+            .alignment(Alignment::Center)
+            .position(Position::Bottom)
+        )
+        .border_set(border::THICK);
+
+    let rows = exporters.iter().map(|exporter| {
+        Row::new(vec![exporter.name(), exporter.description()])
+    });
+    let table = Table::new(rows, [Constraint::Max(18), Constraint::Min(10)])
+        .column_spacing(1)
+        .style(Style::new().blue())
+        .block(block)
+        .highlight_style(Style::new().reversed())
+        .highlight_symbol(">>");
+
+    f.render_widget(Clear, popup);
+    f.render_stateful_widget(table, popup, &mut app.format_table_state);
+}
+
+fn render_console_picker(f: &mut Frame, app: &mut App, area: Rect) {
+    let popup = centered(area, 60, console::SERVICES.len() as u16 + 4);
+    let block = Block::bordered()
+        .title(Title::from(" Open the console at ".bold()).alignment(Alignment::Center))
+        .title(Title::from(Line::from(vec![
+                " Open ".into(),
+                "<Enter>".blue().bold(),
+                " Cancel ".into(),
+                "<Esc> ".blue().bold(),
+            ]))
+            .alignment(Alignment::Center)
+            .position(Position::Bottom)
+        )
+        .border_set(border::THICK);
+
+    let rows = console::SERVICES.iter().map(|service| {
+        Row::new(vec![service.name, service.path])
+    });
+    let table = Table::new(rows, [Constraint::Max(18), Constraint::Min(10)])
+        .column_spacing(1)
+        .style(Style::new().blue())
+        .block(block)
+        .highlight_style(Style::new().reversed())
+        .highlight_symbol(">>");
+
+    f.render_widget(Clear, popup);
+    f.render_stateful_widget(table, popup, &mut app.console_table_state);
+}
+
+/// A `width` x `height` rectangle in the middle of `area`, clamped to fit.
+fn centered(area: Rect, width: u16, height: u16) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    }
 }
\ No newline at end of file
@@ -0,0 +1,102 @@
+use std::rc::Rc;
+
+use ratatui::{
+    crossterm::event::{KeyCode, KeyEvent}, layout::{Alignment, Constraint, Layout, Rect}, style::{Style, Stylize}, symbols::border, text::Line, widgets::{
+        block::{Position, Title}, Block, Row, Table
+    }, Frame
+};
+
+use crate::{app::App, ConfigOption};
+
+pub fn get_layout(f: &mut Frame) -> Rc<[Rect]> {
+    Layout::horizontal([Constraint::Min(5)]).split(f.size())
+}
+
+pub fn handle_key_events(app: &mut App, key: KeyEvent) -> Result<(), anyhow::Error>{
+    match key.code {
+        KeyCode::Enter => {
+            if let Err(err) = app.save_metadata() {
+                app.credential_message = format!("Could not save account details: {:#}", err);
+                return Err(err);
+            }
+            app.current_page = crate::app::CurrentPage::AccountList;
+        },
+        KeyCode::Esc => {
+            app.editing_metadata = false;
+            app.current_page = crate::app::CurrentPage::AccountList;
+        },
+        KeyCode::Down => {
+            let i = match app.metadata_table_state.selected() {
+                Some(i) if i < app.metadata_form.len() - 1 => i + 1,
+                _ => 0,
+            };
+            app.metadata_table_state.select(Some(i));
+        },
+        KeyCode::Up => {
+            let i = match app.metadata_table_state.selected() {
+                Some(0) | None => app.metadata_form.len() - 1,
+                Some(i) => i - 1,
+            };
+            app.metadata_table_state.select(Some(i));
+        },
+        KeyCode::Char(value) if app.metadata_table_state.selected().is_some() => {
+            let i = app.metadata_table_state.selected().unwrap();
+            app.metadata_form[i].value.push(value);
+        },
+        KeyCode::Backspace if app.metadata_table_state.selected().is_some() => {
+            let i = app.metadata_table_state.selected().unwrap();
+            app.metadata_form[i].value.pop();
+        },
+        _ => {}
+    }
+
+    Ok(())
+}
+
+pub fn render_metadata(f: &mut Frame, app: &mut App, area: Rect) {
+    let instructions = Title::from(Line::from(vec![
+        " Next ".into(),
+        "<Down>".blue().bold(),
+        " Previous ".into(),
+        "<Up>".blue().bold(),
+        " Save ".into(),
+        "<Enter>".blue().bold(),
+        " Cancel ".into(),
+        "<Esc> ".blue().bold(),
+    ]));
+    let title = Title::from(format!(" {} - {} ", app.selected_account.account_name, app.display_account_id(&app.selected_account.account_id)).bold());
+    let block = Block::bordered()
+        .title(title.alignment(Alignment::Center))
+        .title(instructions
+            .alignment(Alignment::Center)
+            .position(Position::Bottom)
+        )
+        .border_set(border::THICK);
+
+    let widths = [
+        Constraint::Max(13),
+        Constraint::Min(10)
+    ];
+
+    let rows = app.metadata_form.iter().map(|option: &ConfigOption| {
+        Row::new(vec![option.name.clone(), option.value.clone()])
+    });
+
+    let table = Table::new(rows, widths)
+        .column_spacing(1)
+        .style(Style::new().blue())
+        .header(
+            Row::new(vec!["Field", "Value"])
+                .style(Style::new().bold())
+        )
+        .footer(if app.credential_message.is_empty() {
+            Row::new(vec!["".to_string(), "Tags are separated by commas; per-role settings are written as Role=value, Role=value".to_string()])
+        } else {
+            Row::new(vec!["".to_string(), app.credential_message.clone()]).style(Style::new().red())
+        })
+        .block(block)
+        .highlight_style(Style::new().reversed())
+        .highlight_symbol(">>");
+
+    f.render_stateful_widget(table, area, &mut app.metadata_table_state);
+}
@@ -0,0 +1,113 @@
+use std::rc::Rc;
+
+use ratatui::{
+    crossterm::event::{KeyCode, KeyEvent}, layout::{Alignment, Constraint, Layout, Rect}, style::{Style, Stylize}, symbols::border, text::Line, widgets::{
+        block::{Position, Title}, Block, Cell, Row, Table
+    }, Frame
+};
+
+use crate::app::App;
+
+pub fn get_layout(f: &mut Frame) -> Rc<[Rect]> {
+    Layout::horizontal([Constraint::Min(5)]).split(f.size())
+}
+
+pub fn handle_key_events(app: &mut App, key: KeyEvent) -> Result<(), ()>{
+    match key.code {
+        KeyCode::Down if !app.prune_profiles.is_empty() => {
+            let i = match app.prune_table_state.selected() {
+                Some(i) if i < app.prune_profiles.len() - 1 => i + 1,
+                _ => 0,
+            };
+            app.prune_table_state.select(Some(i));
+        },
+        KeyCode::Up if !app.prune_profiles.is_empty() => {
+            let i = match app.prune_table_state.selected() {
+                Some(0) | None => app.prune_profiles.len() - 1,
+                Some(i) => i - 1,
+            };
+            app.prune_table_state.select(Some(i));
+        },
+        KeyCode::Char(' ') => {
+            if let Some(i) = app.prune_table_state.selected() {
+                app.prune_profiles[i].1 = !app.prune_profiles[i].1;
+            }
+        },
+        KeyCode::Enter => {
+            app.preview_prune();
+        },
+        KeyCode::Esc => {
+            app.pruning = false;
+            app.current_page = crate::app::CurrentPage::AccountList;
+        },
+        _ => {}
+    }
+
+    Ok(())
+}
+
+pub fn render_prune(f: &mut Frame, app: &mut App, area: Rect) {
+    let instructions = Title::from(Line::from(vec![
+        " Next ".into(),
+        "<Down>".blue().bold(),
+        " Previous ".into(),
+        "<Up>".blue().bold(),
+        " Select ".into(),
+        "<Space>".blue().bold(),
+        " Review ".into(),
+        "<Enter>".blue().bold(),
+        " Back ".into(),
+        "<Esc> ".blue().bold(),
+    ]));
+    let block = Block::bordered()
+        .title(Title::from(format!(" Assumer profiles ({}) ", app.prune_profiles.len()).bold()).alignment(Alignment::Center))
+        .title(instructions
+            .alignment(Alignment::Center)
+            .position(Position::Bottom)
+        )
+        .border_set(border::THICK);
+
+    let widths = [
+        Constraint::Max(3),
+        Constraint::Max(11),
+        Constraint::Min(20),
+        Constraint::Max(14),
+        Constraint::Min(10),
+        Constraint::Max(12),
+    ];
+
+    let rows = app.prune_profiles.iter().map(|(profile, selected)| {
+        let status = Cell::from(profile.status.to_string());
+        let status = if profile.status.is_stale() { status.yellow() } else { status.green() };
+        Row::new(vec![
+            Cell::from(if *selected { "[x]" } else { "[ ]" }),
+            Cell::from(profile.file.file_name()),
+            Cell::from(app.display_account_ids(&profile.section)),
+            Cell::from(app.display_account_id(&profile.account_id)),
+            Cell::from(profile.role.clone()),
+            status,
+        ])
+    });
+
+    let footer = if !app.credential_message.is_empty() {
+        Row::new(vec!["".to_string(), "".to_string(), app.credential_message.clone()]).style(Style::new().yellow())
+    } else if app.prune_profiles.is_empty() {
+        Row::new(vec!["".to_string(), "".to_string(), "No assumer profiles found".to_string()])
+    } else {
+        Row::new(vec!["".to_string(), "".to_string(), "Stale profiles are selected; roles are checked against the roles loaded so far".to_string()])
+    };
+
+    let table = Table::new(rows, widths)
+        .column_spacing(1)
+        .style(Style::new().blue())
+        .header(
+            Row::new(vec!["", "File", "Profile", "Account ID", "Role", "Status"])
+                .style(Style::new().bold())
+        )
+        .footer(footer)
+        .block(block)
+        .highlight_style(Style::new().reversed())
+        .highlight_symbol(">>");
+
+    f.render_stateful_widget(table, area, &mut app.prune_table_state);
+}
@@ -0,0 +1,65 @@
+use std::rc::Rc;
+
+use ratatui::{
+    crossterm::event::{KeyCode, KeyEvent}, layout::{Alignment, Constraint, Layout, Rect}, style::Stylize, symbols::border, text::Line, widgets::{
+        block::{Position, Title}, Block, Paragraph
+    }, Frame
+};
+
+use crate::{app::App, profiles};
+
+pub fn get_layout(f: &mut Frame) -> Rc<[Rect]> {
+    Layout::horizontal([Constraint::Min(5)]).split(f.size())
+}
+
+pub fn handle_key_events(app: &mut App, key: KeyEvent) -> Result<(), ()>{
+    let Some(review) = app.review.as_mut() else { return Ok(()) };
+    match key.code {
+        KeyCode::Down => review.scroll = review.scroll.saturating_add(1),
+        KeyCode::Up => review.scroll = review.scroll.saturating_sub(1),
+        KeyCode::PageDown => review.scroll = review.scroll.saturating_add(20),
+        KeyCode::PageUp => review.scroll = review.scroll.saturating_sub(20),
+        KeyCode::Enter => app.apply_review(),
+        KeyCode::Esc => app.review = None,
+        _ => {}
+    }
+
+    Ok(())
+}
+
+pub fn render_review(f: &mut Frame, app: &mut App, area: Rect) {
+    let Some(review) = &app.review else { return };
+    let instructions = Title::from(Line::from(vec![
+        " Scroll ".into(),
+        "<Up/Down>".blue().bold(),
+        " Write ".into(),
+        "<Enter>".red().bold(),
+        " Cancel ".into(),
+        "<Esc> ".blue().bold(),
+    ]));
+    let block = Block::bordered()
+        .title(Title::from(format!(" {} ", review.title).bold()).alignment(Alignment::Center))
+        .title(instructions
+            .alignment(Alignment::Center)
+            .position(Position::Bottom)
+        )
+        .border_set(border::THICK);
+
+    let mut lines: Vec<Line> = review.warnings.iter()
+        .map(|warning| Line::from(format!("Warning: {}", app.display_account_ids(warning))).yellow())
+        .collect();
+    if !lines.is_empty() {
+        lines.push(Line::from(""));
+    }
+    lines.extend(app.display_account_ids(&profiles::diff(&review.changes)).lines()
+        .map(|line| match line.chars().next() {
+            Some('+') if !line.starts_with("+++") => Line::from(line.to_string()).green(),
+            Some('-') if !line.starts_with("---") => Line::from(line.to_string()).red(),
+            Some('@') => Line::from(line.to_string()).cyan(),
+            _ => Line::from(line.to_string()),
+        }));
+    let paragraph = Paragraph::new(lines)
+        .scroll((review.scroll, 0))
+        .block(block);
+    f.render_widget(paragraph, area);
+}
@@ -24,7 +24,7 @@ pub fn handle_key_events(app: &mut App, key: KeyEvent) -> Result<(), ()>{
             app.is_selected = false;
             app.current_page = crate::app::CurrentPage::AccountList;
         }
-        KeyCode::Right => {
+        KeyCode::Right if !app.selected_account.roles.is_empty() => {
             app.select_role();
             app.current_page = crate::app::CurrentPage::Credentials;
         }        
@@ -51,23 +51,29 @@ pub fn render_roles(f: &mut Frame, app: &mut App, area: Rect) {
         "<Q> ".blue().bold(),
     ]));
     let role_list_title = Title::from(format!(" {} - Roles ", app.selected_account.account_name).bold());        
-    let role_list_block = Block::bordered()
+    let mut role_list_block = Block::bordered()
         .title(role_list_title.alignment(Alignment::Left))   
         .title(instructions
             .alignment(Alignment::Center)
             .position(Position::Bottom)
         )        
         .border_set(border::THICK);
+    if let Some(error) = &app.roles_error {
+        role_list_block = role_list_block.title(Title::from(format!(" Could not list roles: {} ", error).red()).alignment(Alignment::Right));
+    }
 
     let widths = [
         Constraint::Min(10)
     ];
 
-    let rows = app.selected_account.roles.iter().map(|row| {
+    let mut rows = app.selected_account.roles.iter().map(|row| {
         Row::new(vec![
             Cell::from(row.clone())
         ])
-    });    
+    }).collect::<Vec<_>>();
+    if rows.is_empty() {
+        rows.push(Row::new(vec![Cell::from("No roles available")]).style(Style::new().dim().italic()));
+    }
 
     // let mut binding = app.selected_account.clone();
     let table = Table::new(rows, widths)
@@ -0,0 +1,724 @@
+//! Runs the `assumer` binary against the fake AWS endpoints from the
+//! `fake-aws` crate, with `HOME` pointed at a throwaway directory.
+
+use std::{fs, io::Write, path::Path, process::{Output, Stdio}};
+
+use fake_aws::FakeAws;
+use sha1::{Digest, Sha1};
+use tempfile::TempDir;
+
+const START_URL: &str = "https://example.awsapps.com/start";
+
+struct Sandbox {
+    home: TempDir,
+    fake: FakeAws,
+}
+
+impl Sandbox {
+    /// Writes a config pointing every endpoint at `fake`, plus a cached SSO
+    /// token so no device authorization (and no browser) is needed.
+    fn new(fake: FakeAws) -> Self {
+        let home = tempfile::tempdir().unwrap();
+        let aws_dir = home.path().join(".aws");
+        let url = fake.url();
+
+        write(&home.path().join(".assumer").join("config.ini"), &format!(
+            "[Main]\n\
+             start_url={START_URL}\n\
+             region=us-east-1\n\
+             aws_config_path={}\n\
+             retry_max_attempts=3\n\
+             retry_max_backoff_seconds=1\n\
+             sso_endpoint_url={url}\n\
+             oidc_endpoint_url={url}\n\
+             sts_endpoint_url={url}\n\
+             federation_endpoint_url={url}/federation\n",
+            aws_dir.display(),
+        ));
+
+        let cache_key = format!("{:02x}", Sha1::digest("sso-example"));
+        write(&aws_dir.join("sso").join("cache").join(format!("{}.json", cache_key)), &format!(
+            r#"{{"startUrl":"{START_URL}","region":"us-east-1","accessToken":"{}","expiresAt":"2099-01-01T00:00:00Z","clientId":"fake-client-id","clientSecret":"fake-client-secret","registrationExpiresAt":"2099-01-01T00:00:00Z","refreshToken":"{}"}}"#,
+            fake_aws::ACCESS_TOKEN,
+            fake_aws::REFRESH_TOKEN,
+        ));
+
+        Sandbox { home, fake }
+    }
+
+    /// Adds a setting to the `[Main]` section of the sandbox config.
+    fn configure(&self, name: &str, value: &str) {
+        let path = self.home.path().join(".assumer").join("config.ini");
+        let config = fs::read_to_string(&path).unwrap();
+        fs::write(&path, format!("{}{}={}\n", config, name, value)).unwrap();
+    }
+
+    fn assumer(&self, args: &[&str]) -> Output {
+        std::process::Command::new(env!("CARGO_BIN_EXE_assumer"))
+            .args(args)
+            .env("HOME", self.home.path())
+            .output()
+            .unwrap()
+    }
+
+    /// Runs assumer with `input` on stdin, e.g. answers to prompts.
+    fn assumer_with_input(&self, args: &[&str], input: &str) -> Output {
+        let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_assumer"))
+            .args(args)
+            .env("HOME", self.home.path())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+        child.wait_with_output().unwrap()
+    }
+}
+
+fn write(path: &Path, contents: &str) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, contents).unwrap();
+}
+
+fn stdout(output: &Output) -> String {
+    assert!(output.status.success(), "assumer failed: {}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+fn two_accounts() -> FakeAws {
+    FakeAws::builder()
+        .account("222222222222", "production", "prod@example.com", &["ReadOnlyAccess"])
+        .account("111111111111", "sandbox", "sandbox@example.com", &["AdministratorAccess", "ReadOnlyAccess"])
+        .start()
+}
+
+#[test]
+fn lists_accounts_across_pages() {
+    let sandbox = Sandbox::new(
+        FakeAws::builder()
+            .account("333333333333", "staging", "staging@example.com", &["ReadOnlyAccess"])
+            .account("111111111111", "sandbox", "sandbox@example.com", &["ReadOnlyAccess"])
+            .account("222222222222", "production", "prod@example.com", &["ReadOnlyAccess"])
+            .page_size(1)
+            .start(),
+    );
+
+    let output = sandbox.assumer(&["accounts"]);
+
+    assert_eq!(
+        stdout(&output),
+        "222222222222\tproduction\tprod@example.com\n\
+         111111111111\tsandbox\tsandbox@example.com\n\
+         333333333333\tstaging\tstaging@example.com\n"
+    );
+    let list_calls = sandbox.fake.requests().iter().filter(|op| *op == "ListAccounts").count();
+    assert_eq!(list_calls, 3);
+}
+
+#[test]
+fn lists_roles_by_account_name() {
+    let sandbox = Sandbox::new(two_accounts());
+
+    let output = sandbox.assumer(&["roles", "sandbox"]);
+
+    assert_eq!(stdout(&output), "AdministratorAccess\nReadOnlyAccess\n");
+}
+
+#[test]
+fn finds_accounts_by_email() {
+    let sandbox = Sandbox::new(two_accounts());
+
+    let output = sandbox.assumer(&["roles", "prod@example.com"]);
+
+    assert_eq!(stdout(&output), "ReadOnlyAccess\n");
+}
+
+#[test]
+fn finds_accounts_by_alias() {
+    let sandbox = Sandbox::new(two_accounts());
+    write(
+        &sandbox.home.path().join(".assumer/accounts.json"),
+        r#"{"111111111111": {"alias": "play", "tags": ["team-a"]}}"#,
+    );
+
+    let output = sandbox.assumer(&["roles", "PLAY"]);
+
+    assert_eq!(stdout(&output), "AdministratorAccess\nReadOnlyAccess\n");
+}
+
+#[test]
+fn prints_role_credentials() {
+    let sandbox = Sandbox::new(two_accounts());
+
+    let output = sandbox.assumer(&["credentials", "111111111111", "ReadOnlyAccess"]);
+
+    let stdout = stdout(&output);
+    assert!(stdout.contains("export AWS_ACCESS_KEY_ID=ASIAFAKE111111111111\n"));
+    assert!(stdout.contains("export AWS_SECRET_ACCESS_KEY=fake-secret-111111111111-ReadOnlyAccess\n"));
+    assert!(stdout.contains("export AWS_SESSION_TOKEN=fake-session-111111111111-ReadOnlyAccess\n"));
+}
+
+#[test]
+fn uses_account_and_role_regions() {
+    let sandbox = Sandbox::new(two_accounts());
+    sandbox.configure("sso_region", "us-east-1");
+    sandbox.configure("region", "eu-west-1");
+    write(
+        &sandbox.home.path().join(".assumer/accounts.json"),
+        r#"{"111111111111": {"region": "eu-central-1", "role_regions": {"AdministratorAccess": "ap-southeast-2"}}}"#,
+    );
+
+    let read_only = stdout(&sandbox.assumer(&["credentials", "sandbox", "ReadOnlyAccess"]));
+    let admin = stdout(&sandbox.assumer(&["credentials", "sandbox", "AdministratorAccess"]));
+    let production = stdout(&sandbox.assumer(&["credentials", "production", "ReadOnlyAccess"]));
+
+    assert!(read_only.contains("export AWS_REGION=eu-central-1\n"));
+    assert!(admin.contains("export AWS_REGION=ap-southeast-2\n"));
+    assert!(production.contains("export AWS_REGION=eu-west-1\n"));
+
+    stdout(&sandbox.assumer(&["sync-profiles", "--yes"]));
+    let config = fs::read_to_string(sandbox.home.path().join(".aws").join("config")).unwrap();
+    assert!(config.contains("sso_region = us-east-1\n"));
+    assert!(config.contains("[profile sandbox-AdministratorAccess]\nsso_session = sso-example\nsso_account_id = 111111111111\nsso_role_name = AdministratorAccess\nregion = ap-southeast-2\n"));
+    assert!(config.contains("sso_role_name = ReadOnlyAccess\nregion = eu-central-1\n"));
+}
+
+#[test]
+fn assumes_chained_roles() {
+    let sandbox = Sandbox::new(two_accounts());
+    write(
+        &sandbox.home.path().join(".assumer/accounts.json"),
+        r#"{"111111111111": {"chained_roles": [{
+            "name": "deployer",
+            "source_role": "ReadOnlyAccess",
+            "role_arn": "arn:aws:iam::333333333333:role/Deployer",
+            "session_name": "{name} from {account_name}",
+            "external_id": "ext-42"
+        }]}}"#,
+    );
+
+    let roles = stdout(&sandbox.assumer(&["roles", "sandbox"]));
+    let output = stdout(&sandbox.assumer(&["credentials", "sandbox", "deployer"]));
+
+    assert_eq!(roles, "AdministratorAccess\nReadOnlyAccess\nReadOnlyAccess > deployer\n");
+    assert!(output.contains("export AWS_ACCESS_KEY_ID=ASIACHAIN333333333333\n"));
+    assert!(output.contains("export AWS_SESSION_TOKEN=fake-chained-session-deployer-from-sandbox-ext-42\n"));
+    assert!(sandbox.fake.requests().ends_with(&["GetRoleCredentials".to_string(), "AssumeRole".to_string()]));
+}
+
+#[test]
+fn assumes_chained_roles_again_after_they_change() {
+    let sandbox = Sandbox::new(two_accounts());
+    sandbox.configure("cache_credentials_on_disk", "true");
+    let accounts_path = sandbox.home.path().join(".assumer/accounts.json");
+    let chained_role = |external_id: &str| format!(
+        r#"{{"111111111111": {{"chained_roles": [{{
+            "name": "deployer",
+            "source_role": "ReadOnlyAccess",
+            "role_arn": "arn:aws:iam::333333333333:role/Deployer",
+            "external_id": "{}"
+        }}]}}}}"#,
+        external_id
+    );
+
+    write(&accounts_path, &chained_role("ext-42"));
+    stdout(&sandbox.assumer(&["credentials", "sandbox", "deployer"]));
+    let cached = stdout(&sandbox.assumer(&["credentials", "sandbox", "deployer"]));
+    write(&accounts_path, &chained_role("ext-43"));
+    let changed = stdout(&sandbox.assumer(&["credentials", "sandbox", "deployer"]));
+
+    assert!(cached.contains("-ext-42\n"));
+    assert!(changed.contains("-ext-43\n"));
+    assert_eq!(sandbox.fake.assume_role_requests().len(), 2);
+}
+
+#[test]
+fn assumes_chained_roles_with_mfa_and_session_tags() {
+    let sandbox = Sandbox::new(two_accounts());
+    write(
+        &sandbox.home.path().join(".assumer/accounts.json"),
+        r#"{"111111111111": {"chained_roles": [{
+            "name": "breakglass",
+            "source_role": "ReadOnlyAccess",
+            "role_arn": "arn:aws:iam::333333333333:role/BreakGlass",
+            "mfa_serial": "arn:aws:iam::111111111111:mfa/me",
+            "tags": {"team": "platform", "ticket": "OPS-7"}
+        }]}}"#,
+    );
+
+    let output = sandbox.assumer_with_input(&["credentials", "sandbox", "breakglass"], "123456\n");
+
+    assert!(stdout(&output).contains("export AWS_ACCESS_KEY_ID=ASIACHAIN333333333333\n"));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("MFA code for arn:aws:iam::111111111111:mfa/me:"));
+    let requests = sandbox.fake.assume_role_requests();
+    let form = requests.last().unwrap();
+    assert_eq!(form["SerialNumber"], "arn:aws:iam::111111111111:mfa/me");
+    assert_eq!(form["TokenCode"], "123456");
+    assert_eq!(form["Tags.member.1.Key"], "team");
+    assert_eq!(form["Tags.member.1.Value"], "platform");
+    assert_eq!(form["Tags.member.2.Key"], "ticket");
+    assert_eq!(form["Tags.member.2.Value"], "OPS-7");
+}
+
+#[test]
+fn warns_about_credentials_close_to_expiry() {
+    let sandbox = Sandbox::new(two_accounts());
+    sandbox.configure("expiry_warning_minutes", "120");
+
+    let output = sandbox.assumer(&["credentials", "sandbox", "ReadOnlyAccess"]);
+    let exported = sandbox.assumer(&["export-as", "dotenv", "sandbox", "ReadOnlyAccess"]);
+
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Warning: credentials are close to expiry (expires in "));
+    assert!(String::from_utf8_lossy(&exported.stderr).contains("Warning: credentials are close to expiry (expires in "));
+}
+
+#[test]
+fn reuses_cached_role_credentials() {
+    let sandbox = Sandbox::new(two_accounts());
+    sandbox.configure("cache_credentials_on_disk", "true");
+
+    let first = stdout(&sandbox.assumer(&["credential-process", "sandbox", "ReadOnlyAccess"]));
+    let second = stdout(&sandbox.assumer(&["credential-process", "sandbox", "ReadOnlyAccess"]));
+
+    assert!(first.contains(r#""AccessKeyId":"ASIAFAKE111111111111""#));
+    assert_eq!(first, second);
+    let fetches = sandbox.fake.requests().iter().filter(|op| *op == "GetRoleCredentials").count();
+    assert_eq!(fetches, 1);
+}
+
+#[test]
+fn writes_only_the_target_credentials_profile() {
+    let sandbox = Sandbox::new(two_accounts());
+    let credentials_path = sandbox.home.path().join(".aws").join("credentials");
+    let original = "# managed by hand\n\
+                    [personal]\n\
+                    aws_access_key_id = AKIAPERSONAL\n\
+                    \n\
+                    [sandbox]\n\
+                    region = eu-west-1\n\
+                    AWS_ACCESS_KEY_ID = stale\n";
+    write(&credentials_path, original);
+
+    stdout(&sandbox.assumer(&["export", "sandbox", "ReadOnlyAccess", "sandbox"]));
+
+    let updated = fs::read_to_string(&credentials_path).unwrap();
+    assert!(updated.starts_with("# managed by hand\n[personal]\naws_access_key_id = AKIAPERSONAL\n\n[sandbox]\nregion = eu-west-1\n"));
+    assert!(updated.contains("aws_access_key_id = ASIAFAKE111111111111\n"));
+    assert!(updated.contains("aws_session_token = fake-session-111111111111-ReadOnlyAccess\n"));
+    assert!(updated.contains("x_security_token_expires = "));
+    assert!(!updated.contains("AWS_ACCESS_KEY_ID"));
+    let backup = credentials_path.with_file_name("credentials.assumer-backup");
+    assert_eq!(fs::read_to_string(&backup).unwrap(), original);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        assert_eq!(fs::metadata(&backup).unwrap().permissions().mode() & 0o777, 0o600);
+    }
+}
+
+#[test]
+fn refuses_to_replace_profiles_assumer_did_not_write() {
+    let sandbox = Sandbox::new(two_accounts());
+    let credentials_path = sandbox.home.path().join(".aws").join("credentials");
+    let original = "[default]\n\
+                    aws_access_key_id = AKIALONGTERM\n\
+                    aws_secret_access_key = long-term-secret\n";
+    write(&credentials_path, original);
+
+    let refused = sandbox.assumer(&["export", "sandbox", "ReadOnlyAccess"]);
+    let unchanged = fs::read_to_string(&credentials_path).unwrap();
+    stdout(&sandbox.assumer(&["export", "sandbox", "ReadOnlyAccess", "--force"]));
+
+    assert!(!refused.status.success());
+    assert!(String::from_utf8_lossy(&refused.stderr).contains("wasn't written by assumer, pass --force to replace it"));
+    assert_eq!(unchanged, original);
+    assert!(fs::read_to_string(&credentials_path).unwrap().contains("aws_access_key_id = ASIAFAKE111111111111\n"));
+    let backup = credentials_path.with_file_name("credentials.assumer-backup");
+    assert_eq!(fs::read_to_string(&backup).unwrap(), original);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        assert_eq!(fs::metadata(&backup).unwrap().permissions().mode() & 0o777, 0o600);
+    }
+}
+
+#[test]
+fn prunes_stale_assumer_profiles() {
+    let sandbox = Sandbox::new(two_accounts());
+    let config_path = sandbox.home.path().join(".aws").join("config");
+    write(&config_path, "[default]\n\
+                         region = eu-west-1\n\
+                         \n\
+                         [profile 'assumer-sandbox/ReadOnlyAccess']\n\
+                         sso_account_id = 111111111111\n\
+                         sso_role_name = ReadOnlyAccess\n\
+                         \n\
+                         [profile 'assumer-retired/ReadOnlyAccess']\n\
+                         sso_account_id = 999999999999\n\
+                         sso_role_name = ReadOnlyAccess\n\
+                         \n\
+                         [profile sandbox-billing]\n\
+                         sso_account_id = 111111111111\n\
+                         sso_role_name = BillingAccess\n\
+                         x_assumer_managed = true\n");
+    write(&sandbox.home.path().join(".aws").join("credentials"), "[old]\n\
+                         aws_access_key_id = ASIAOLD\n\
+                         aws_secret_access_key = old-secret-access-key\n\
+                         aws_session_token = old-session-token-value\n\
+                         x_security_token_expires = 2020-01-01T00:00:00Z\n\
+                         x_assumer_managed = true\n\
+                         x_assumer_account_id = 222222222222\n\
+                         x_assumer_role = ReadOnlyAccess\n");
+
+    let output = stdout(&sandbox.assumer(&["prune", "--yes"]));
+
+    assert!(output.contains("config\t[profile 'assumer-sandbox/ReadOnlyAccess']\tcurrent\n"));
+    assert!(output.contains("config\t[profile 'assumer-retired/ReadOnlyAccess']\taccount gone\n"));
+    assert!(output.contains("config\t[profile sandbox-billing]\trole gone\n"));
+    assert!(output.contains("credentials\t[old]\texpired\n"));
+    assert!(output.contains("-sso_account_id = 999999999999\n"));
+    assert!(output.contains("-aws_secret_access_key = old-************-key\n"));
+    assert!(!output.contains("old-secret-access-key") && !output.contains("old-session-token-value"));
+    assert!(output.contains("Removed 3 profiles"));
+    assert_eq!(
+        fs::read_to_string(&config_path).unwrap(),
+        "[default]\nregion = eu-west-1\n\n[profile 'assumer-sandbox/ReadOnlyAccess']\nsso_account_id = 111111111111\nsso_role_name = ReadOnlyAccess\n"
+    );
+    assert_eq!(fs::read_to_string(sandbox.home.path().join(".aws").join("credentials")).unwrap(), "");
+    let backup = sandbox.home.path().join(".aws").join("credentials.assumer-backup");
+    assert!(fs::read_to_string(&backup).unwrap().contains("aws_access_key_id = ASIAOLD\n"));
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        assert_eq!(fs::metadata(&backup).unwrap().permissions().mode() & 0o777, 0o600);
+    }
+}
+
+#[test]
+fn migrates_profiles_to_the_naming_template() {
+    let sandbox = Sandbox::new(two_accounts());
+    sandbox.configure("profile_name_template", "{sso}/{account_name} {role}");
+    sandbox.configure("profile_name_case", "lower");
+    let config_path = sandbox.home.path().join(".aws").join("config");
+    write(&config_path, "# work accounts\n\
+                         [profile 'assumer-sandbox/ReadOnlyAccess']\n\
+                         sso_account_id = 111111111111\n\
+                         sso_role_name = ReadOnlyAccess\n\
+                         \n\
+                         [profile 'assumer-production/ReadOnlyAccess']\n\
+                         sso_account_id = 222222222222\n\
+                         sso_role_name = ReadOnlyAccess\n\
+                         \n\
+                         [profile sso-example-production-readonlyaccess]\n\
+                         region = eu-west-1\n");
+
+    let output = sandbox.assumer(&["migrate-profiles", "--yes"]);
+
+    assert!(String::from_utf8_lossy(&output.stderr).contains("[profile 'assumer-production/ReadOnlyAccess'] can't become [profile sso-example-production-readonlyaccess]"));
+    assert!(stdout(&output).contains("Migrated 1 profiles"));
+    let migrated = fs::read_to_string(&config_path).unwrap();
+    assert!(migrated.starts_with("# work accounts\n[profile sso-example-sandbox-readonlyaccess]\nsso_account_id = 111111111111\n"));
+    assert!(migrated.contains("[profile 'assumer-production/ReadOnlyAccess']\n"));
+}
+
+#[test]
+fn skips_accounts_whose_roles_cannot_be_listed() {
+    let sandbox = Sandbox::new(two_accounts());
+    sandbox.fake.forbid_role_list("222222222222");
+    let config_path = sandbox.home.path().join(".aws").join("config");
+
+    let output = sandbox.assumer(&["sync-profiles", "--yes"]);
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success());
+    assert!(stderr.contains("Warning: could not list the roles in production (222222222222)"));
+    assert!(stderr.contains("Skipped 1 accounts whose roles could not be listed: production (222222222222)"));
+    assert!(stdout(&output).contains("Wrote 2 profiles"));
+    let synced = fs::read_to_string(&config_path).unwrap();
+    assert!(synced.contains("sso_account_id = 111111111111\n"));
+    assert!(!synced.contains("222222222222"));
+}
+
+#[test]
+fn syncs_a_profile_for_every_role() {
+    let sandbox = Sandbox::new(two_accounts());
+    let config_path = sandbox.home.path().join(".aws").join("config");
+    write(&config_path, "# hand-written\n\
+                         [profile production-ReadOnlyAccess]\n\
+                         region = eu-west-1\n\
+                         \n\
+                         [profile 'assumer-sandbox/ReadOnlyAccess']\n\
+                         sso_account_id = 111111111111\n\
+                         sso_role_name = ReadOnlyAccess\n");
+
+    let dry_run = stdout(&sandbox.assumer(&["sync-profiles", "--dry-run"]));
+    assert!(dry_run.contains("+[profile sandbox-AdministratorAccess]\n"));
+    assert!(dry_run.contains("-[profile 'assumer-sandbox/ReadOnlyAccess']\n"));
+    assert!(fs::read_to_string(&config_path).unwrap().contains("assumer-sandbox"));
+
+    let output = sandbox.assumer(&["sync-profiles", "--yes"]);
+
+    assert!(String::from_utf8_lossy(&output.stderr).contains("production/ReadOnlyAccess would be [profile production-ReadOnlyAccess]"));
+    assert!(stdout(&output).contains("Wrote 2 profiles"));
+    let synced = fs::read_to_string(&config_path).unwrap();
+    assert!(synced.starts_with("# hand-written\n[profile production-ReadOnlyAccess]\nregion = eu-west-1\n\n\
+                                [sso-session sso-example]\n\
+                                sso_start_url = https://example.awsapps.com/start\n\
+                                sso_region = us-east-1\n\
+                                sso_registration_scopes = sso:account:access\n\n\
+                                [profile sandbox-AdministratorAccess]\n"));
+    assert!(synced.contains("[profile sandbox-ReadOnlyAccess]\nsso_session = sso-example\nsso_account_id = 111111111111\nsso_role_name = ReadOnlyAccess\n"));
+    assert!(!synced.contains("assumer-sandbox"));
+    assert!(stdout(&sandbox.assumer(&["sync-profiles", "--yes"])).contains("All 2 profiles are up to date"));
+
+    sandbox.configure("profile_format", "legacy");
+    stdout(&sandbox.assumer(&["sync-profiles", "--yes"]));
+
+    let legacy = fs::read_to_string(&config_path).unwrap();
+    assert!(legacy.contains("[profile sandbox-ReadOnlyAccess]\nsso_account_id = 111111111111\nsso_role_name = ReadOnlyAccess\nregion = us-east-1\noutput = json\nx_assumer_managed = true\nsso_start_url = https://example.awsapps.com/start\nsso_region = us-east-1\n"));
+    assert!(!legacy.contains("sso_session = "));
+}
+
+#[cfg(unix)]
+#[test]
+fn opens_arns_with_a_custom_browser_command() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let sandbox = Sandbox::new(two_accounts());
+    let script = sandbox.home.path().join("browser.sh");
+    let opened = sandbox.home.path().join("opened");
+    write(&script, &format!("#!/bin/sh\necho \"$@\" > {}.tmp && mv {0}.tmp {0}\n", opened.display()));
+    fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+    sandbox.configure("browser", "custom");
+    sandbox.configure("browser_command", &format!("{} {{container}} {{url}}", script.display()));
+
+    stdout(&sandbox.assumer(&["console", "--arn", "arn:aws:lambda:eu-west-1:111111111111:function:my-function"]));
+
+    let started = std::time::Instant::now();
+    while !opened.exists() && started.elapsed() < std::time::Duration::from_secs(5) {
+        std::thread::sleep(std::time::Duration::from_millis(20));
+    }
+    let opened = fs::read_to_string(&opened).unwrap();
+    assert!(opened.starts_with("aws-sso-111111111111-ReadOnlyAccess http://"));
+    assert!(opened.contains("Action=login"));
+    assert!(opened.contains("Destination=https%3A%2F%2Fconsole.aws.amazon.com%2Flambda%2Fhome%3Fregion%3Deu-west-1%23%2Ffunctions%2Fmy-function"));
+}
+
+#[test]
+fn prints_the_console_sign_in_url() {
+    let sandbox = Sandbox::new(two_accounts());
+    let federation = format!("{}/federation?Action=login", sandbox.fake.url());
+
+    let printed = stdout(&sandbox.assumer(&["console", "sandbox", "ReadOnlyAccess", "s3/home", "--print"]));
+    let linked = stdout(&sandbox.assumer(&["console", "--link", "sandbox", "ReadOnlyAccess"]));
+
+    assert!(printed.starts_with(&federation));
+    assert!(printed.contains("Destination=https%3A%2F%2Fconsole.aws.amazon.com%2Fs3%2Fhome%3Fregion%3Dus-east-1&SigninToken="));
+    assert!(linked.starts_with(&format!("\x1b]8;;{}", federation)));
+    assert!(linked.ends_with("\x1b\\AWS console: sandbox as ReadOnlyAccess\x1b]8;;\x1b\\\n"));
+}
+
+#[test]
+fn opens_configured_console_destinations() {
+    let sandbox = Sandbox::new(two_accounts());
+    sandbox.configure("console_destination", "iam/home");
+    write(
+        &sandbox.home.path().join(".assumer/accounts.json"),
+        r#"{"111111111111": {
+            "console_destination": "cloudwatch/home#dashboards",
+            "role_console_destinations": {"AdministratorAccess": "https://console.aws.amazon.com/ec2/home#Instances:instanceState=running,stopped"}
+        }}"#,
+    );
+
+    let account = stdout(&sandbox.assumer(&["console", "sandbox", "ReadOnlyAccess", "--print"]));
+    let role = stdout(&sandbox.assumer(&["console", "sandbox", "AdministratorAccess", "--print"]));
+    let configured = stdout(&sandbox.assumer(&["console", "production", "ReadOnlyAccess", "--print"]));
+    let picked = stdout(&sandbox.assumer(&["console", "sandbox", "AdministratorAccess", "s3/home", "--print"]));
+
+    assert!(account.contains("Destination=https%3A%2F%2Fconsole.aws.amazon.com%2Fcloudwatch%2Fhome%3Fregion%3Dus-east-1%23dashboards&"));
+    assert!(role.contains("Destination=https%3A%2F%2Fconsole.aws.amazon.com%2Fec2%2Fhome%23Instances%3AinstanceState%3Drunning%2Cstopped&"));
+    assert!(configured.contains("Destination=https%3A%2F%2Fconsole.aws.amazon.com%2Fiam%2Fhome%3Fregion%3Dus-east-1&"));
+    assert!(picked.contains("Destination=https%3A%2F%2Fconsole.aws.amazon.com%2Fs3%2Fhome%3Fregion%3Dus-east-1&"));
+}
+
+#[test]
+fn validates_console_sessions() {
+    let sandbox = Sandbox::new(two_accounts());
+    sandbox.configure("console_issuer", "https://intranet.example.com/aws");
+    write(
+        &sandbox.home.path().join(".assumer/accounts.json"),
+        r#"{"111111111111": {"role_console_session_seconds": {"AdministratorAccess": 60}, "chained_roles": [{
+            "name": "deployer",
+            "source_role": "ReadOnlyAccess",
+            "role_arn": "arn:aws:iam::333333333333:role/Deployer"
+        }]}}"#,
+    );
+
+    let printed = stdout(&sandbox.assumer(&["console", "sandbox", "ReadOnlyAccess", "--print"]));
+    let too_short = sandbox.assumer(&["console", "sandbox", "AdministratorAccess", "--print"]);
+    sandbox.configure("console_session_seconds", "7200");
+    let production = sandbox.assumer(&["console", "production", "ReadOnlyAccess", "--print"]);
+    let chained = sandbox.assumer(&["console", "sandbox", "deployer", "--print"]);
+
+    assert!(printed.contains("Issuer=https%3A%2F%2Fintranet.example.com%2Faws&"));
+    assert!(String::from_utf8_lossy(&too_short.stderr).contains("Console sessions for SSO roles last 900 to 43200 seconds, not 60"));
+    assert!(production.status.success());
+    assert!(String::from_utf8_lossy(&chained.stderr).contains("Console sessions for chained roles last 900 to 3600 seconds, not 7200"));
+}
+
+#[test]
+fn uses_the_partition_of_the_sso_region() {
+    let sandbox = Sandbox::new(two_accounts());
+    sandbox.configure("sso_region", "us-gov-west-1");
+
+    let printed = stdout(&sandbox.assumer(&["console", "sandbox", "ReadOnlyAccess", "s3/home", "--print"]));
+    let commercial_arn = sandbox.assumer(&["console", "--arn", "arn:aws:s3:::my-bucket", "--account", "sandbox", "--print"]);
+
+    assert!(printed.contains("Destination=https%3A%2F%2Fconsole.amazonaws-us-gov.com%2Fs3%2Fhome%3Fregion%3Dus-gov-west-1&"));
+    assert!(String::from_utf8_lossy(&commercial_arn.stderr).contains("arn:aws:s3:::my-bucket is in the aws partition, but you sign in to aws-us-gov"));
+
+    sandbox.configure("sso_region", "cn-north-1");
+    let output = sandbox.assumer(&["accounts"]);
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("is in the aws partition but the SSO region cn-north-1 is in aws-cn"));
+}
+
+#[test]
+fn copies_credentials_through_osc52() {
+    let sandbox = Sandbox::new(two_accounts());
+    sandbox.configure("clipboard", "osc52");
+    sandbox.configure("clipboard_clear_seconds", "0");
+
+    let output = stdout(&sandbox.assumer(&["copy", "sandbox", "ReadOnlyAccess", "access-key-id"]));
+
+    // base64("ASIAFAKE111111111111")
+    assert!(output.contains("]52;c;QVNJQUZBS0UxMTExMTExMTExMTE=\x07"));
+}
+
+#[test]
+fn exports_kubernetes_secrets() {
+    let sandbox = Sandbox::new(two_accounts());
+
+    let output = stdout(&sandbox.assumer(&["export-as", "k8s-secret", "sandbox", "ReadOnlyAccess"]));
+
+    assert!(output.starts_with("apiVersion: v1\nkind: Secret\nmetadata:\n  name: aws-credentials-111111111111-readonlyaccess\n"));
+    assert!(output.contains("  AWS_ACCESS_KEY_ID: \"ASIAFAKE111111111111\"\n"));
+}
+
+#[test]
+fn escapes_names_and_values_in_exports() {
+    let sandbox = Sandbox::new(
+        FakeAws::builder()
+            .account("111111111111", "lab \"$(id)\" `x`", "lab@example.com", &["Ops'Admin"])
+            .start(),
+    );
+    write(&sandbox.home.path().join(".assumer/accounts.json"), r#"{"111111111111": {"region": "eu-west-1 \"$HOME\" 100%"}}"#);
+
+    let manifest = stdout(&sandbox.assumer(&["export-as", "k8s-secret", "111111111111", "Ops'Admin"]));
+    let powershell = stdout(&sandbox.assumer(&["export-as", "powershell", "111111111111", "Ops'Admin"]));
+    let shell = stdout(&sandbox.assumer(&["export-as", "shell", "111111111111", "Ops'Admin"]));
+    let fish = stdout(&sandbox.assumer(&["export-as", "fish", "111111111111", "Ops'Admin"]));
+    let cmd = stdout(&sandbox.assumer(&["export-as", "cmd", "111111111111", "Ops'Admin"]));
+    let dotenv = stdout(&sandbox.assumer(&["export-as", "dotenv", "111111111111", "Ops'Admin"]));
+
+    assert!(manifest.contains("    assumer/account: \"lab \\\"$(id)\\\" `x` (111111111111)\"\n"));
+    assert!(manifest.contains("    assumer/role: \"Ops'Admin\"\n"));
+    assert!(powershell.contains("$Env:AWS_SECRET_ACCESS_KEY = 'fake-secret-111111111111-Ops''Admin'\n"));
+    assert!(shell.contains("export AWS_ACCESS_KEY_ID=ASIAFAKE111111111111\n"));
+    assert!(shell.contains("export AWS_SECRET_ACCESS_KEY='fake-secret-111111111111-Ops'\\''Admin'\n"));
+    assert!(shell.contains("export AWS_REGION='eu-west-1 \"$HOME\" 100%'\n"));
+    assert!(fish.contains("set -gx AWS_SECRET_ACCESS_KEY 'fake-secret-111111111111-Ops\\'Admin'\n"));
+    assert!(cmd.contains("set \"AWS_SECRET_ACCESS_KEY=fake-secret-111111111111-Ops'Admin\"\n"));
+    assert!(dotenv.contains("AWS_SECRET_ACCESS_KEY=\"fake-secret-111111111111-Ops'Admin\"\n"));
+    assert!(cmd.contains("set \"AWS_REGION=eu-west-1 \"$HOME\" 100%%\"\n"));
+    assert!(dotenv.contains("AWS_REGION=\"eu-west-1 \\\"$HOME\\\" 100%\"\n"));
+}
+
+#[test]
+fn appends_to_github_env_files() {
+    let sandbox = Sandbox::new(two_accounts());
+    let github_env = sandbox.home.path().join("github_env");
+    write(&github_env, "EXISTING=1\n");
+
+    stdout(&sandbox.assumer(&["export-as", "github-actions", "sandbox", "ReadOnlyAccess", github_env.to_str().unwrap()]));
+
+    let contents = fs::read_to_string(&github_env).unwrap();
+    assert!(contents.starts_with("EXISTING=1\nAWS_ACCESS_KEY_ID=ASIAFAKE111111111111\n"));
+}
+
+#[test]
+fn masks_secrets_in_github_actions_logs() {
+    let sandbox = Sandbox::new(two_accounts());
+    let github_env = sandbox.home.path().join("github_env");
+
+    let written = stdout(&sandbox.assumer(&["export-as", "github-actions", "sandbox", "ReadOnlyAccess", github_env.to_str().unwrap()]));
+    let printed = sandbox.assumer(&["export-as", "github-actions", "sandbox", "ReadOnlyAccess"]);
+
+    let masks = "::add-mask::fake-secret-111111111111-ReadOnlyAccess\n::add-mask::fake-session-111111111111-ReadOnlyAccess\n";
+    assert!(written.starts_with(masks));
+    assert!(String::from_utf8_lossy(&printed.stderr).starts_with(masks));
+    assert!(stdout(&printed).starts_with("AWS_ACCESS_KEY_ID=ASIAFAKE111111111111\n"));
+    assert!(!fs::read_to_string(&github_env).unwrap().contains("::add-mask::"));
+}
+
+#[test]
+fn refreshes_the_cached_token() {
+    let sandbox = Sandbox::new(two_accounts());
+
+    stdout(&sandbox.assumer(&["accounts"]));
+
+    assert_eq!(sandbox.fake.requests().first().map(String::as_str), Some("CreateToken"));
+}
+
+#[test]
+fn retries_throttled_calls() {
+    let sandbox = Sandbox::new(two_accounts());
+    sandbox.fake.throttle_next(2);
+
+    let output = sandbox.assumer(&["accounts"]);
+
+    assert_eq!(stdout(&output).lines().count(), 2);
+    let list_calls = sandbox.fake.requests().iter().filter(|op| *op == "ListAccounts").count();
+    assert_eq!(list_calls, 3);
+}
+
+#[test]
+fn reports_persistent_throttling() {
+    let sandbox = Sandbox::new(two_accounts());
+    sandbox.fake.throttle_next(100);
+
+    let output = sandbox.assumer(&["accounts"]);
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Throttled by AWS"));
+}
+
+#[test]
+fn rejects_unknown_accounts() {
+    let sandbox = Sandbox::new(two_accounts());
+
+    let output = sandbox.assumer(&["roles", "staging"]);
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No account matching staging"));
+}
+
+#[test]
+fn ignores_expired_inventory_caches() {
+    let sandbox = Sandbox::new(two_accounts());
+    let cache_key = format!("{:02x}", Sha1::digest(START_URL));
+    write(
+        &sandbox.home.path().join(".assumer/cache").join(format!("inventory-{}.json", cache_key)),
+        &format!(
+            r#"{{"startUrl":"{START_URL}","syncedAt":"2020-01-01T00:00:00Z","accounts":[{{"account_name":"retired","account_id":"333333333333","roles":["ReadOnlyAccess"]}}]}}"#,
+        ),
+    );
+
+    let output = sandbox.assumer(&["roles", "retired"]);
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No account matching retired"));
+}